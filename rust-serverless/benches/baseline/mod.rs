@@ -0,0 +1,306 @@
+//! The searches as they were before they used pooled workspaces: visited
+//! nodes, parents and path counts in hash maps allocated for every query,
+//! and per-query parent arrays for the single path search. Kept to measure
+//! the workspaces against, on node indices rather than page ids.
+
+use rayon::prelude::*;
+use rust_serverless::adjacency::ArchivedAdjacency;
+use rust_serverless::graph::ArchivedCsrGraph;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+trait SearchRecord: Default {
+    fn root(&mut self, node: u32);
+    fn discover(&mut self, node: u32, parent: u32);
+    fn rediscover(&mut self, node: u32, parent: u32);
+}
+
+#[derive(Default)]
+struct ParentLists(FxHashMap<u32, Vec<u32>>);
+
+impl SearchRecord for ParentLists {
+    fn root(&mut self, _node: u32) {}
+
+    fn discover(&mut self, node: u32, parent: u32) {
+        self.0.insert(node, vec![parent]);
+    }
+
+    fn rediscover(&mut self, node: u32, parent: u32) {
+        self.0.get_mut(&node).unwrap().push(parent);
+    }
+}
+
+#[derive(Default)]
+struct PathCounts(FxHashMap<u32, u128>);
+
+impl SearchRecord for PathCounts {
+    fn root(&mut self, node: u32) {
+        self.0.insert(node, 1);
+    }
+
+    fn discover(&mut self, node: u32, parent: u32) {
+        let count = self.0[&parent];
+        self.0.insert(node, count);
+    }
+
+    fn rediscover(&mut self, node: u32, parent: u32) {
+        let count = self.0[&parent];
+        let node_count = self.0.get_mut(&node).unwrap();
+        *node_count = node_count.saturating_add(count);
+    }
+}
+
+struct BidirectionalSearch<R> {
+    start_node: u32,
+    end_node: u32,
+    forward: R,
+    backward: R,
+    meeting_nodes: Vec<u32>,
+}
+
+fn bidirectional_bfs<R: SearchRecord>(graph: &ArchivedCsrGraph, start_node: u32, end_node: u32) -> Option<BidirectionalSearch<R>> {
+    let mut forward = R::default();
+    forward.root(start_node);
+    let mut backward = R::default();
+    backward.root(end_node);
+    if start_node == end_node {
+        return Some(BidirectionalSearch { start_node, end_node, forward, backward, meeting_nodes: vec![start_node] });
+    }
+
+    let mut forward_frontier = FxHashSet::from_iter([start_node]);
+    let mut backward_frontier = FxHashSet::from_iter([end_node]);
+    let mut forward_dist = FxHashMap::from_iter([(start_node, 0)]);
+    let mut backward_dist = FxHashMap::from_iter([(end_node, 0)]);
+    let mut meeting_nodes = FxHashSet::default();
+    let mut shortest_path_len = u32::MAX;
+    let mut forward_depth = 0;
+    let mut backward_depth = 0;
+
+    while !forward_frontier.is_empty() && !backward_frontier.is_empty() {
+        if forward_depth + backward_depth >= shortest_path_len {
+            break;
+        }
+        let forward_link_count: usize = forward_frontier.par_iter().map(|&u| graph.links.degree(u)).sum();
+        let backward_link_count: usize = backward_frontier.par_iter().map(|&u| graph.reverse_links.degree(u)).sum();
+        let (links, frontier, dist, other_dist, depth, record) = if forward_link_count <= backward_link_count {
+            forward_depth += 1;
+            (&graph.links, &mut forward_frontier, &mut forward_dist, &backward_dist, forward_depth, &mut forward)
+        } else {
+            backward_depth += 1;
+            (&graph.reverse_links, &mut backward_frontier, &mut backward_dist, &forward_dist, backward_depth, &mut backward)
+        };
+        *frontier = expand_level(links, frontier, dist, depth, record);
+
+        for &node in frontier.iter() {
+            if let Some(&other) = other_dist.get(&node) {
+                let path_len = depth + other;
+                if path_len < shortest_path_len {
+                    shortest_path_len = path_len;
+                    meeting_nodes.clear();
+                    meeting_nodes.insert(node);
+                } else if path_len == shortest_path_len {
+                    meeting_nodes.insert(node);
+                }
+            }
+        }
+    }
+
+    if meeting_nodes.is_empty() {
+        return None;
+    }
+    let mut meeting_nodes: Vec<u32> = meeting_nodes.into_iter().collect();
+    meeting_nodes.sort_unstable();
+    Some(BidirectionalSearch { start_node, end_node, forward, backward, meeting_nodes })
+}
+
+fn expand_level<R: SearchRecord>(
+    links: &ArchivedAdjacency,
+    frontier: &FxHashSet<u32>,
+    dist: &mut FxHashMap<u32, u32>,
+    depth: u32,
+    record: &mut R,
+) -> FxHashSet<u32> {
+    let mut next_frontier = FxHashSet::with_capacity_and_hasher(frontier.len() * 5, Default::default());
+    for &u in frontier {
+        for v in links.neighbours(u) {
+            match dist.get(&v) {
+                None => {
+                    dist.insert(v, depth);
+                    record.discover(v, u);
+                    next_frontier.insert(v);
+                }
+                Some(&dist) if dist == depth => record.rediscover(v, u),
+                Some(_) => {}
+            }
+        }
+    }
+    next_frontier
+}
+
+/// Up to `limit` shortest paths between two nodes and their total number.
+pub fn find_all_shortest_path(graph: &ArchivedCsrGraph, start_node: u32, end_node: u32, limit: usize) -> (Vec<Vec<u32>>, u128) {
+    let Some(search) = bidirectional_bfs::<ParentLists>(graph, start_node, end_node) else {
+        return (Vec::new(), 0);
+    };
+    let (forward, backward) = (&search.forward.0, &search.backward.0);
+    let (start_node, end_node) = (search.start_node, search.end_node);
+    let parents = |parents, node, root| ParentPaths { parents, root, stack: vec![(node, 0)], started: false };
+    let paths = search
+        .meeting_nodes
+        .iter()
+        .flat_map(|&meet_node| {
+            parents(forward, meet_node, start_node).flat_map(move |mut forward_path| {
+                forward_path.reverse();
+                parents(backward, meet_node, end_node).map(move |backward_path| {
+                    let mut path = forward_path.clone();
+                    path.extend_from_slice(&backward_path[1..]);
+                    path
+                })
+            })
+        })
+        .take(limit)
+        .collect();
+
+    let mut forward_counts = FxHashMap::default();
+    let mut backward_counts = FxHashMap::default();
+    let num_paths = search
+        .meeting_nodes
+        .iter()
+        .map(|&meet_node| {
+            let forward_paths = count_parent_paths(meet_node, start_node, forward, &mut forward_counts);
+            let backward_paths = count_parent_paths(meet_node, end_node, backward, &mut backward_counts);
+            forward_paths.saturating_mul(backward_paths)
+        })
+        .fold(0, u128::saturating_add);
+    (paths, num_paths)
+}
+
+/// Number of shortest paths between two nodes.
+pub fn count_shortest_path(graph: &ArchivedCsrGraph, start_node: u32, end_node: u32) -> u128 {
+    let Some(search) = bidirectional_bfs::<PathCounts>(graph, start_node, end_node) else {
+        return 0;
+    };
+    search
+        .meeting_nodes
+        .iter()
+        .map(|meet_node| search.forward.0[meet_node].saturating_mul(search.backward.0[meet_node]))
+        .fold(0, u128::saturating_add)
+}
+
+fn count_parent_paths(node: u32, root: u32, parents: &FxHashMap<u32, Vec<u32>>, counts: &mut FxHashMap<u32, u128>) -> u128 {
+    if node == root {
+        return 1;
+    }
+    if let Some(&count) = counts.get(&node) {
+        return count;
+    }
+    let count = parents.get(&node).map_or(0, |parent_nodes| {
+        parent_nodes
+            .iter()
+            .map(|&parent_node| count_parent_paths(parent_node, root, parents, counts))
+            .fold(0, u128::saturating_add)
+    });
+    counts.insert(node, count);
+    count
+}
+
+struct ParentPaths<'a> {
+    parents: &'a FxHashMap<u32, Vec<u32>>,
+    root: u32,
+    stack: Vec<(u32, usize)>,
+    started: bool,
+}
+
+impl ParentPaths<'_> {
+    fn descend(&mut self) -> Option<Vec<u32>> {
+        while let Some(&(node, choice)) = self.stack.last() {
+            if node == self.root {
+                return Some(self.stack.iter().map(|&(node, _)| node).collect());
+            }
+            let parent_node = *self.parents.get(&node)?.get(choice)?;
+            self.stack.push((parent_node, 0));
+        }
+        None
+    }
+}
+
+impl Iterator for ParentPaths<'_> {
+    type Item = Vec<u32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            return self.descend();
+        }
+        self.stack.pop();
+        while let Some((node, choice)) = self.stack.last_mut() {
+            *choice += 1;
+            if self.parents.get(node).is_some_and(|parent_nodes| *choice < parent_nodes.len()) {
+                return self.descend();
+            }
+            self.stack.pop();
+        }
+        None
+    }
+}
+
+const UNVISITED: u32 = u32::MAX;
+
+/// A single shortest path between two nodes, with parent arrays allocated for the query.
+pub fn find_shortest_path(graph: &ArchivedCsrGraph, start_node: u32, end_node: u32) -> Vec<u32> {
+    if start_node == end_node {
+        return vec![start_node];
+    }
+    let node_count = graph.links.node_count() as usize;
+    let mut forward_parent = vec![UNVISITED; node_count];
+    let mut backward_parent = vec![UNVISITED; node_count];
+    forward_parent[start_node as usize] = start_node;
+    backward_parent[end_node as usize] = end_node;
+    let mut forward_frontier = vec![start_node];
+    let mut backward_frontier = vec![end_node];
+    let mut meeting_node = None;
+
+    while meeting_node.is_none() && !forward_frontier.is_empty() && !backward_frontier.is_empty() {
+        let forward_link_count: usize = forward_frontier.iter().map(|&u| graph.links.degree(u)).sum();
+        let backward_link_count: usize = backward_frontier.iter().map(|&u| graph.reverse_links.degree(u)).sum();
+        meeting_node = if forward_link_count <= backward_link_count {
+            expand_single_parent(&graph.links, &mut forward_frontier, &mut forward_parent, &backward_parent)
+        } else {
+            expand_single_parent(&graph.reverse_links, &mut backward_frontier, &mut backward_parent, &forward_parent)
+        };
+    }
+    let Some(meeting_node) = meeting_node else {
+        return Vec::new();
+    };
+
+    let mut path = vec![meeting_node];
+    let mut node = meeting_node;
+    while node != start_node {
+        node = forward_parent[node as usize];
+        path.push(node);
+    }
+    path.reverse();
+    let mut node = meeting_node;
+    while node != end_node {
+        node = backward_parent[node as usize];
+        path.push(node);
+    }
+    path
+}
+
+fn expand_single_parent(links: &ArchivedAdjacency, frontier: &mut Vec<u32>, parent: &mut [u32], other_parent: &[u32]) -> Option<u32> {
+    let mut next_frontier = Vec::with_capacity(frontier.len() * 5);
+    for &u in frontier.iter() {
+        for v in links.neighbours(u) {
+            if parent[v as usize] != UNVISITED {
+                continue;
+            }
+            parent[v as usize] = u;
+            if other_parent[v as usize] != UNVISITED {
+                return Some(v);
+            }
+            next_frontier.push(v);
+        }
+    }
+    *frontier = next_frontier;
+    None
+}
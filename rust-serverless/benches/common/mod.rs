@@ -0,0 +1,75 @@
+//! Helpers shared by the benchmarks.
+
+use rust_serverless::adjacency::Adjacency;
+use rust_serverless::graph::{CsrGraph, GraphMeta, GRAPH_FORMAT_VERSION};
+use std::collections::HashMap;
+
+const MAX_OUT_DEGREE: u32 = 40;
+
+/// Deterministic xorshift generator so every run benches the same inputs.
+pub struct XorShift(pub u64);
+
+impl XorShift {
+    pub fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 32) as u32
+    }
+}
+
+/// Offsets and edges of a random graph where link targets are skewed toward
+/// low indices, giving a few hubs like the real Wikipedia graph.
+pub fn synthetic_links(rng: &mut XorShift, node_count: u32) -> (Vec<u32>, Vec<u32>) {
+    let mut offsets = vec![0];
+    let mut edges = Vec::new();
+    for _ in 0..node_count {
+        let out_degree = rng.next() % MAX_OUT_DEGREE + 1;
+        let mut links: Vec<u32> = (0..out_degree)
+            .map(|_| ((rng.next() as f64 / u32::MAX as f64).powi(3) * node_count as f64) as u32 % node_count)
+            .collect();
+        links.sort_unstable();
+        links.dedup();
+        edges.extend_from_slice(&links);
+        offsets.push(edges.len() as u32);
+    }
+    (offsets, edges)
+}
+
+/// Graph with the given outgoing links and the incoming links derived from them.
+pub fn csr_graph(offsets: Vec<u32>, edges: Vec<u32>, page_ids: Vec<u32>, sorted_nodes: Vec<u32>) -> CsrGraph {
+    let node_count = page_ids.len() as u32;
+    let mut reverse_adjacency: Vec<Vec<u32>> = vec![Vec::new(); node_count as usize];
+    for (source, node_offsets) in offsets.windows(2).enumerate() {
+        for &target in &edges[node_offsets[0] as usize..node_offsets[1] as usize] {
+            reverse_adjacency[target as usize].push(source as u32);
+        }
+    }
+    let mut reverse_offsets = vec![0];
+    let mut reverse_edges = Vec::new();
+    for links in &reverse_adjacency {
+        reverse_edges.extend_from_slice(links);
+        reverse_offsets.push(reverse_edges.len() as u32);
+    }
+
+    CsrGraph {
+        meta: GraphMeta {
+            format_version: GRAPH_FORMAT_VERSION,
+            lang: "bench".to_string(),
+            dumps: Vec::new(),
+            builder_version: String::new(),
+            built_at: 0,
+            build_duration_ms: 0,
+            node_count,
+            edge_count: edges.len() as u64,
+        },
+        links: Adjacency::Raw { offsets, edges },
+        reverse_links: Adjacency::Raw { offsets: reverse_offsets, edges: reverse_edges },
+        page_ids,
+        sorted_nodes,
+        pages: HashMap::new(),
+        redirects: HashMap::new(),
+        titles: vec![String::new(); node_count as usize],
+        title_index: Vec::new(),
+    }
+}
@@ -0,0 +1,142 @@
+//! Query latency on real graph files, to compare builds of the same dumps,
+//! for example with and without `REORDER_NODES`.
+//!
+//! Run with `LATENCY_GRAPHS=graph.rkyv,reordered.rkyv cargo bench --bench latency`.
+//! Without `LATENCY_GRAPHS`, a synthetic graph is compared in page id order
+//! and in each order of the builder instead. Pairs are drawn by page id, so
+//! every graph answers the same queries whatever its node order.
+
+mod common;
+#[allow(dead_code)]
+#[path = "../../sql-dump-to-rust/src/graph/reorder.rs"]
+mod reorder;
+
+use common::{csr_graph, synthetic_links, XorShift};
+use criterion::{criterion_group, criterion_main, BenchmarkGroup, BenchmarkId, Criterion};
+use criterion::measurement::WallTime;
+use reorder::NodeOrder;
+use rkyv::rancor;
+use rust_serverless::graph::{page_id, ArchivedCsrGraph, CsrGraph};
+use rust_serverless::loader::LoadedGraph;
+use rust_serverless::shortest_path::{count_shortest_path, find_shortest_path};
+use std::hint::black_box;
+use std::path::PathBuf;
+
+const PAIR_COUNT: usize = 64;
+const SYNTHETIC_NODE_COUNT: u32 = 500_000;
+
+fn latency_benchmark(c: &mut Criterion) {
+    let graph_paths = std::env::var("LATENCY_GRAPHS").unwrap_or_default();
+    let graph_paths: Vec<&str> = graph_paths.split(',').map(str::trim).filter(|path| !path.is_empty()).collect();
+    if graph_paths.is_empty() {
+        synthetic_latency_benchmark(c);
+        return;
+    }
+    let graphs: Vec<LoadedGraph> = graph_paths
+        .into_iter()
+        .map(|path| LoadedGraph::load(&PathBuf::from(path)).unwrap_or_else(|err| panic!("{}", err)))
+        .collect();
+
+    let first_graph = graphs[0].graph();
+    let page_ids: Vec<u32> = (0..first_graph.links.node_count()).map(|index| page_id(first_graph, index)).collect();
+    let pairs = random_pairs(page_ids);
+
+    let mut group = c.benchmark_group("latency");
+    for loaded in &graphs {
+        let name = loaded.path().file_name().map_or_else(
+            || loaded.path().display().to_string(),
+            |file_name| file_name.to_string_lossy().into_owned(),
+        );
+        bench_graph(&mut group, &name, loaded.graph(), &pairs);
+    }
+    group.finish();
+}
+
+/// The same synthetic graph in page id order and reordered like the builder
+/// does. Its hubs are spread over random page ids first, as in real dumps
+/// where page ids follow creation dates rather than links.
+fn synthetic_latency_benchmark(c: &mut Criterion) {
+    let mut rng = XorShift(0x5eed_cafe_f00d_beef);
+    let (offsets, edges) = synthetic_links(&mut rng, SYNTHETIC_NODE_COUNT);
+    let mut shuffle: Vec<u32> = (0..SYNTHETIC_NODE_COUNT).collect();
+    for index in (1..shuffle.len()).rev() {
+        shuffle.swap(index, rng.next() as usize % (index + 1));
+    }
+    let (offsets, edges) = reorder::relabel_links(&offsets, &edges, &shuffle, &reorder::inverse(&shuffle));
+    let page_ids: Vec<u32> = (0..SYNTHETIC_NODE_COUNT).map(|index| index * 3 + 1).collect();
+
+    let graphs: Vec<(NodeOrder, rkyv::util::AlignedVec)> = [NodeOrder::PageId, NodeOrder::Degree, NodeOrder::Bfs]
+        .into_iter()
+        .map(|order| {
+            let graph = if order == NodeOrder::PageId {
+                csr_graph(offsets.clone(), edges.clone(), page_ids.clone(), Vec::new())
+            } else {
+                reordered_graph(&offsets, &edges, &page_ids, order)
+            };
+            (order, rkyv::to_bytes::<rancor::Error>(&graph).expect("Failed to serialize synthetic graph"))
+        })
+        .collect();
+    let pairs = random_pairs(page_ids);
+    let graphs: Vec<(NodeOrder, &ArchivedCsrGraph)> = graphs
+        .iter()
+        .map(|(order, bytes)| (*order, rkyv::access::<ArchivedCsrGraph, rancor::Error>(bytes).expect("Failed to access synthetic graph")))
+        .collect();
+    for &(from, to) in &pairs {
+        let num_paths = count_shortest_path(graphs[0].1, from, to).num_paths;
+        for &(order, graph) in &graphs[1..] {
+            assert_eq!(count_shortest_path(graph, from, to).num_paths, num_paths, "{:?} order from {} to {}", order, from, to);
+        }
+    }
+
+    let mut group = c.benchmark_group("latency_synthetic");
+    for &(order, graph) in &graphs {
+        bench_graph(&mut group, &format!("{:?}", order), graph, &pairs);
+    }
+    group.finish();
+}
+
+/// A graph in page id order renumbered the way sql-dump-to-rust does.
+fn reordered_graph(offsets: &[u32], edges: &[u32], page_ids: &[u32], order: NodeOrder) -> CsrGraph {
+    let nodes = reorder::node_order(offsets, edges, order);
+    let new_index = reorder::inverse(&nodes);
+    let (offsets, edges) = reorder::relabel_links(offsets, edges, &nodes, &new_index);
+    let page_ids = reorder::permute(&mut page_ids.to_vec(), &nodes);
+    csr_graph(offsets, edges, page_ids, new_index)
+}
+
+/// Random pairs among `page_ids`, the same whatever their order.
+fn random_pairs(mut page_ids: Vec<u32>) -> Vec<(u32, u32)> {
+    page_ids.sort_unstable();
+    let mut rng = XorShift(0x5eed_cafe_f00d_beef);
+    (0..PAIR_COUNT)
+        .map(|_| {
+            let from = page_ids[rng.next() as usize % page_ids.len()];
+            let to = page_ids[rng.next() as usize % page_ids.len()];
+            (from, to)
+        })
+        .collect()
+}
+
+fn bench_graph(group: &mut BenchmarkGroup<'_, WallTime>, name: &str, graph: &ArchivedCsrGraph, pairs: &[(u32, u32)]) {
+    group.bench_function(BenchmarkId::new("find_shortest_path", name), |b| {
+        b.iter(|| {
+            for &(from, to) in pairs {
+                black_box(find_shortest_path(graph, from, to));
+            }
+        })
+    });
+    group.bench_function(BenchmarkId::new("count_shortest_path", name), |b| {
+        b.iter(|| {
+            for &(from, to) in pairs {
+                black_box(count_shortest_path(graph, from, to));
+            }
+        })
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = latency_benchmark
+}
+criterion_main!(benches);
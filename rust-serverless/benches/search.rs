@@ -0,0 +1,98 @@
+//! Latency of the shortest path searches on a synthetic graph, next to the
+//! searches they replaced in `baseline`.
+//!
+//! Run with `cargo bench --bench search`.
+
+mod baseline;
+mod common;
+
+use common::{csr_graph, synthetic_links, XorShift};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rkyv::rancor;
+use rust_serverless::graph::ArchivedCsrGraph;
+use rust_serverless::shortest_path::{count_shortest_path, find_all_shortest_path, find_shortest_path};
+use std::hint::black_box;
+
+const NODE_COUNT: u32 = 500_000;
+const PAIR_COUNT: usize = 32;
+
+fn search_benchmark(c: &mut Criterion) {
+    let mut rng = XorShift(0x5eed_cafe_f00d_beef);
+    let (offsets, edges) = synthetic_links(&mut rng, NODE_COUNT);
+    let mut graph = csr_graph(offsets, edges, (0..NODE_COUNT).map(|index| index * 3 + 1).collect(), Vec::new());
+    let raw_bytes = rkyv::to_bytes::<rancor::Error>(&graph).expect("Failed to serialize synthetic graph");
+    graph.links = graph.links.compress();
+    graph.reverse_links = graph.reverse_links.compress();
+    let compressed_bytes = rkyv::to_bytes::<rancor::Error>(&graph).expect("Failed to serialize synthetic graph");
+    drop(graph);
+
+    let pairs: Vec<(u32, u32)> = (0..PAIR_COUNT)
+        .map(|_| ((rng.next() % NODE_COUNT) * 3 + 1, (rng.next() % NODE_COUNT) * 3 + 1))
+        .collect();
+
+    for (group_name, bytes) in [("search", &raw_bytes), ("search_compressed", &compressed_bytes)] {
+        let graph = rkyv::access::<ArchivedCsrGraph, rancor::Error>(bytes).expect("Failed to access synthetic graph");
+        bench_searches(c, group_name, graph, &pairs);
+    }
+}
+
+/// Benches every search as it runs now, with pooled workspaces, and as it
+/// ran before with per-query hash maps and arrays.
+fn bench_searches(c: &mut Criterion, group_name: &str, graph: &ArchivedCsrGraph, pairs: &[(u32, u32)]) {
+    let nodes: Vec<(u32, u32)> = pairs.iter().map(|&(from, to)| ((from - 1) / 3, (to - 1) / 3)).collect();
+    for (&(from, to), &(from_node, to_node)) in pairs.iter().zip(&nodes) {
+        assert_eq!(count_shortest_path(graph, from, to).num_paths, baseline::count_shortest_path(graph, from_node, to_node));
+    }
+
+    let mut group = c.benchmark_group(group_name);
+    group.bench_function(BenchmarkId::new("find_shortest_path", PAIR_COUNT), |b| {
+        b.iter(|| {
+            for &(from, to) in pairs {
+                black_box(find_shortest_path(graph, from, to));
+            }
+        })
+    });
+    group.bench_function(BenchmarkId::new("find_shortest_path_before_workspaces", PAIR_COUNT), |b| {
+        b.iter(|| {
+            for &(from, to) in &nodes {
+                black_box(baseline::find_shortest_path(graph, from, to));
+            }
+        })
+    });
+    group.bench_function(BenchmarkId::new("count_shortest_path", PAIR_COUNT), |b| {
+        b.iter(|| {
+            for &(from, to) in pairs {
+                black_box(count_shortest_path(graph, from, to));
+            }
+        })
+    });
+    group.bench_function(BenchmarkId::new("count_shortest_path_before_workspaces", PAIR_COUNT), |b| {
+        b.iter(|| {
+            for &(from, to) in &nodes {
+                black_box(baseline::count_shortest_path(graph, from, to));
+            }
+        })
+    });
+    group.bench_function(BenchmarkId::new("find_all_shortest_path_limit_10", PAIR_COUNT), |b| {
+        b.iter(|| {
+            for &(from, to) in pairs {
+                black_box(find_all_shortest_path(graph, from, to, Some(10)));
+            }
+        })
+    });
+    group.bench_function(BenchmarkId::new("find_all_shortest_path_limit_10_before_workspaces", PAIR_COUNT), |b| {
+        b.iter(|| {
+            for &(from, to) in &nodes {
+                black_box(baseline::find_all_shortest_path(graph, from, to, 10));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = search_benchmark
+}
+criterion_main!(benches);
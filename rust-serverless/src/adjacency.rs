@@ -0,0 +1,384 @@
+use rkyv::rend::u32_le;
+use rkyv::{Archive, Deserialize, Serialize};
+use std::fmt;
+
+/// The links of one direction of the graph.
+///
+/// The builder writes this layout with its own copy of the type and of the
+/// varint functions, in `sql-dump-to-rust/src/graph/adjacency.rs`, so any
+/// change to the encoding must be made to both files.
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+pub enum Adjacency {
+    /// Neighbours of node `i` are `edges[offsets[i]..offsets[i + 1]]`.
+    Raw { offsets: Vec<u32>, edges: Vec<u32> },
+    /// Neighbours of node `i` start at byte `offsets[i]`, varint encoded: the
+    /// degree, then each sorted neighbour as its gap minus one from the
+    /// previous neighbour, the first one counting from -1.
+    Compressed { offsets: Vec<u64>, edge_count: u64, bytes: Vec<u8> },
+}
+
+impl Adjacency {
+    /// The compressed encoding of the same links, sorted and deduplicated.
+    pub fn compress(self) -> Adjacency {
+        let Adjacency::Raw { offsets, edges } = self else {
+            return self;
+        };
+
+        let mut compressed_offsets = Vec::with_capacity(offsets.len());
+        let mut bytes = Vec::new();
+        let mut edge_count = 0;
+        let mut neighbours = Vec::new();
+        for node_offsets in offsets.windows(2) {
+            neighbours.clear();
+            neighbours.extend_from_slice(&edges[node_offsets[0] as usize..node_offsets[1] as usize]);
+            neighbours.sort_unstable();
+            neighbours.dedup();
+
+            compressed_offsets.push(bytes.len() as u64);
+            edge_count += neighbours.len() as u64;
+            write_varint(&mut bytes, neighbours.len() as u32);
+            let mut previous = u32::MAX;
+            for &neighbour in &neighbours {
+                write_varint(&mut bytes, neighbour - previous.wrapping_add(1));
+                previous = neighbour;
+            }
+        }
+        compressed_offsets.push(bytes.len() as u64);
+
+        Adjacency::Compressed { offsets: compressed_offsets, edge_count, bytes }
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+#[inline]
+fn read_varint(bytes: &[u8], position: &mut usize) -> u32 {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[*position];
+        *position += 1;
+        value |= ((byte & 0x7f) as u32) << shift;
+        if byte < 0x80 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+/// `read_varint` for bytes that were not checked yet: `None` when the varint
+/// runs past `end` or does not fit in a u32.
+fn read_varint_checked(bytes: &[u8], position: &mut usize, end: usize) -> Option<u32> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        if *position >= end {
+            return None;
+        }
+        let byte = *bytes.get(*position)?;
+        *position += 1;
+        if shift == 28 && byte > 0x0f {
+            return None;
+        }
+        value |= ((byte & 0x7f) as u32) << shift;
+        if byte < 0x80 {
+            return Some(value);
+        }
+        shift += 7;
+    }
+}
+
+/// Why the links of a graph that passed archive validation still cannot be searched.
+#[derive(Debug, PartialEq)]
+pub enum InvalidAdjacency {
+    /// There is not one offset per node plus the end offset.
+    NodeCount { expected: u32, found: usize },
+    /// The links of a node end before they start or past the edges.
+    Offsets { node: u32 },
+    /// The varints of a node run past its bytes, overflow a u32 or do not fill them.
+    Varints { node: u32 },
+    /// A node links to a node that does not exist.
+    Neighbour { node: u32, neighbour: u64 },
+    /// The stored edge count is not the number of links decoded.
+    EdgeCount { expected: u64, found: u64 },
+}
+
+impl fmt::Display for InvalidAdjacency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidAdjacency::NodeCount { expected, found } => {
+                write!(f, "{} offsets for {} nodes", found, expected)
+            }
+            InvalidAdjacency::Offsets { node } => write!(f, "offsets of node {} are out of range", node),
+            InvalidAdjacency::Varints { node } => write!(f, "links of node {} are not valid varints", node),
+            InvalidAdjacency::Neighbour { node, neighbour } => {
+                write!(f, "node {} links to node {} which does not exist", node, neighbour)
+            }
+            InvalidAdjacency::EdgeCount { expected, found } => {
+                write!(f, "edge count is {} but {} links were decoded", expected, found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidAdjacency {}
+
+impl ArchivedAdjacency {
+    /// Check every offset and link of a graph of `node_count` nodes, so the
+    /// unchecked decoding in `degree` and `neighbours` cannot panic or wrap.
+    pub fn validate(&self, node_count: u32) -> Result<(), InvalidAdjacency> {
+        let offset_count = match self {
+            ArchivedAdjacency::Raw { offsets, .. } => offsets.len(),
+            ArchivedAdjacency::Compressed { offsets, .. } => offsets.len(),
+        };
+        if offset_count != node_count as usize + 1 {
+            return Err(InvalidAdjacency::NodeCount { expected: node_count, found: offset_count });
+        }
+
+        match self {
+            ArchivedAdjacency::Raw { offsets, edges } => {
+                for (node, node_offsets) in offsets.windows(2).enumerate() {
+                    let node = node as u32;
+                    let (start, end) = (node_offsets[0].to_native() as usize, node_offsets[1].to_native() as usize);
+                    if start > end || end > edges.len() {
+                        return Err(InvalidAdjacency::Offsets { node });
+                    }
+                    if let Some(neighbour) = edges[start..end].iter().find(|edge| edge.to_native() >= node_count) {
+                        return Err(InvalidAdjacency::Neighbour { node, neighbour: neighbour.to_native() as u64 });
+                    }
+                }
+            }
+            ArchivedAdjacency::Compressed { offsets, edge_count, bytes } => {
+                let mut decoded = 0;
+                for (node, node_offsets) in offsets.windows(2).enumerate() {
+                    let node = node as u32;
+                    let (start, end) = (node_offsets[0].to_native(), node_offsets[1].to_native());
+                    if start > end || end > bytes.len() as u64 {
+                        return Err(InvalidAdjacency::Offsets { node });
+                    }
+                    let (mut position, end) = (start as usize, end as usize);
+                    let degree = read_varint_checked(bytes, &mut position, end).ok_or(InvalidAdjacency::Varints { node })?;
+                    let mut previous = -1i64;
+                    for _ in 0..degree {
+                        let gap = read_varint_checked(bytes, &mut position, end).ok_or(InvalidAdjacency::Varints { node })?;
+                        previous += gap as i64 + 1;
+                        if previous >= node_count as i64 {
+                            return Err(InvalidAdjacency::Neighbour { node, neighbour: previous as u64 });
+                        }
+                    }
+                    if position != end {
+                        return Err(InvalidAdjacency::Varints { node });
+                    }
+                    decoded += degree as u64;
+                }
+                if decoded != edge_count.to_native() {
+                    return Err(InvalidAdjacency::EdgeCount { expected: edge_count.to_native(), found: decoded });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn node_count(&self) -> u32 {
+        match self {
+            ArchivedAdjacency::Raw { offsets, .. } => (offsets.len() - 1) as u32,
+            ArchivedAdjacency::Compressed { offsets, .. } => (offsets.len() - 1) as u32,
+        }
+    }
+
+    pub fn edge_count(&self) -> usize {
+        match self {
+            ArchivedAdjacency::Raw { edges, .. } => edges.len(),
+            ArchivedAdjacency::Compressed { edge_count, .. } => edge_count.to_native() as usize,
+        }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        matches!(self, ArchivedAdjacency::Compressed { .. })
+    }
+
+    #[inline]
+    pub fn degree(&self, node: u32) -> usize {
+        match self {
+            ArchivedAdjacency::Raw { offsets, .. } => {
+                (offsets[(node + 1) as usize].to_native() - offsets[node as usize].to_native()) as usize
+            }
+            ArchivedAdjacency::Compressed { offsets, bytes, .. } => {
+                let mut position = offsets[node as usize].to_native() as usize;
+                read_varint(bytes, &mut position) as usize
+            }
+        }
+    }
+
+    /// Neighbours of `node`, decoded on the fly for the compressed encoding.
+    #[inline]
+    pub fn neighbours(&self, node: u32) -> Neighbours<'_> {
+        match self {
+            ArchivedAdjacency::Raw { offsets, edges } => {
+                let start_offset = offsets[node as usize].to_native() as usize;
+                let end_offset = offsets[(node + 1) as usize].to_native() as usize;
+                Neighbours::Raw(edges[start_offset..end_offset].iter())
+            }
+            ArchivedAdjacency::Compressed { offsets, bytes, .. } => {
+                let mut position = offsets[node as usize].to_native() as usize;
+                let remaining = read_varint(bytes, &mut position);
+                Neighbours::Compressed { bytes, position, remaining, previous: u32::MAX }
+            }
+        }
+    }
+}
+
+/// Iterator over the neighbours of a node in either encoding.
+pub enum Neighbours<'a> {
+    Raw(std::slice::Iter<'a, u32_le>),
+    Compressed { bytes: &'a [u8], position: usize, remaining: u32, previous: u32 },
+}
+
+impl Iterator for Neighbours<'_> {
+    type Item = u32;
+
+    #[inline]
+    fn next(&mut self) -> Option<u32> {
+        match self {
+            Neighbours::Raw(edges) => edges.next().map(|edge| edge.to_native()),
+            Neighbours::Compressed { bytes, position, remaining, previous } => {
+                if *remaining == 0 {
+                    return None;
+                }
+                *remaining -= 1;
+                *previous = previous.wrapping_add(1) + read_varint(bytes, position);
+                Some(*previous)
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = match self {
+            Neighbours::Raw(edges) => edges.len(),
+            Neighbours::Compressed { remaining, .. } => *remaining as usize,
+        };
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for Neighbours<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rkyv::rancor;
+
+    #[test]
+    fn varints_round_trip() {
+        let values = [0, 1, 0x7f, 0x80, 0x3fff, 0x4000, (1 << 21) - 1, 1 << 21, (1 << 28) - 1, 1 << 28, u32::MAX];
+        let mut bytes = Vec::new();
+        for value in values {
+            write_varint(&mut bytes, value);
+        }
+        // One byte per started group of 7 bits.
+        assert_eq!(bytes.len(), 1 + 1 + 1 + 2 + 2 + 3 + 3 + 4 + 4 + 5 + 5);
+
+        let mut position = 0;
+        for value in values {
+            assert_eq!(read_varint(&bytes, &mut position), value);
+        }
+        assert_eq!(position, bytes.len());
+    }
+
+    #[test]
+    fn compressed_neighbours_match_raw() {
+        let rows: Vec<Vec<u32>> = vec![
+            vec![],
+            vec![0, 1, 2],
+            vec![],
+            // Gaps needing five byte varints.
+            vec![7, 7 + (1 << 28), (1 << 31) + 3, u32::MAX - 1],
+            // Unsorted and duplicated, as graphs migrated from older formats keep them.
+            vec![9, 4, 9, 0],
+            vec![],
+            vec![0, u32::MAX],
+        ];
+        let mut offsets = vec![0];
+        let mut edges = Vec::new();
+        for row in &rows {
+            edges.extend(row);
+            offsets.push(edges.len() as u32);
+        }
+        let raw = Adjacency::Raw { offsets, edges };
+        let raw_bytes = rkyv::to_bytes::<rancor::Error>(&raw).unwrap();
+        let compressed_bytes = rkyv::to_bytes::<rancor::Error>(&raw.compress()).unwrap();
+        let raw = rkyv::access::<ArchivedAdjacency, rancor::Error>(&raw_bytes).unwrap();
+        let compressed = rkyv::access::<ArchivedAdjacency, rancor::Error>(&compressed_bytes).unwrap();
+
+        assert!(!raw.is_compressed());
+        assert!(compressed.is_compressed());
+        assert_eq!(raw.node_count(), rows.len() as u32);
+        assert_eq!(compressed.node_count(), rows.len() as u32);
+        let mut edge_count = 0;
+        for (node, row) in rows.iter().enumerate() {
+            let node = node as u32;
+            assert_eq!(raw.neighbours(node).collect::<Vec<_>>(), *row);
+            assert_eq!(raw.degree(node), row.len());
+
+            let mut expected = row.clone();
+            expected.sort_unstable();
+            expected.dedup();
+            let neighbours = compressed.neighbours(node);
+            assert_eq!(neighbours.len(), expected.len());
+            assert_eq!(neighbours.collect::<Vec<_>>(), expected, "neighbours of node {}", node);
+            assert_eq!(compressed.degree(node), expected.len());
+            edge_count += expected.len();
+        }
+        assert_eq!(compressed.edge_count(), edge_count);
+    }
+
+    fn archive(adjacency: &Adjacency) -> rkyv::util::AlignedVec {
+        rkyv::to_bytes::<rancor::Error>(adjacency).unwrap()
+    }
+
+    fn validate(bytes: &[u8], node_count: u32) -> Result<(), InvalidAdjacency> {
+        rkyv::access::<ArchivedAdjacency, rancor::Error>(bytes).unwrap().validate(node_count)
+    }
+
+    #[test]
+    fn validate_accepts_built_links() {
+        let raw = Adjacency::Raw { offsets: vec![0, 2, 2, 4], edges: vec![1, 2, 0, 2] };
+        assert_eq!(validate(&archive(&raw), 3), Ok(()));
+        assert_eq!(validate(&archive(&raw.compress()), 3), Ok(()));
+    }
+
+    #[test]
+    fn validate_rejects_corrupt_links() {
+        let raw = |offsets: Vec<u32>, edges: Vec<u32>| archive(&Adjacency::Raw { offsets, edges });
+        assert_eq!(validate(&raw(vec![0, 1], vec![0]), 2), Err(InvalidAdjacency::NodeCount { expected: 2, found: 2 }));
+        assert_eq!(validate(&raw(vec![0, 2, 1], vec![0, 1]), 2), Err(InvalidAdjacency::Offsets { node: 1 }));
+        assert_eq!(validate(&raw(vec![0, 1, 3], vec![0, 1]), 2), Err(InvalidAdjacency::Offsets { node: 1 }));
+        assert_eq!(validate(&raw(vec![0, 1, 1], vec![2]), 2), Err(InvalidAdjacency::Neighbour { node: 0, neighbour: 2 }));
+
+        let compressed = |offsets: Vec<u64>, edge_count: u64, bytes: Vec<u8>| archive(&Adjacency::Compressed { offsets, edge_count, bytes });
+        // Node 0 links to node 1, node 1 to nothing.
+        assert_eq!(validate(&compressed(vec![0, 2, 3], 1, vec![1, 1, 0]), 2), Ok(()));
+        assert_eq!(validate(&compressed(vec![0, 2, 4], 1, vec![1, 1, 0]), 2), Err(InvalidAdjacency::Offsets { node: 1 }));
+        // A degree of two with a single gap, so the varints run into node 1.
+        assert_eq!(validate(&compressed(vec![0, 2, 3], 1, vec![2, 1, 0]), 2), Err(InvalidAdjacency::Varints { node: 0 }));
+        // Bytes left over after the links of node 0.
+        assert_eq!(validate(&compressed(vec![0, 3, 4], 1, vec![1, 1, 0, 0]), 2), Err(InvalidAdjacency::Varints { node: 0 }));
+        // A varint continuing past five bytes, and one overflowing a u32.
+        assert_eq!(
+            validate(&compressed(vec![0, 7, 8], 1, vec![1, 0xff, 0xff, 0xff, 0xff, 0x80, 0, 0]), 2),
+            Err(InvalidAdjacency::Varints { node: 0 })
+        );
+        assert_eq!(
+            validate(&compressed(vec![0, 6, 7], 1, vec![1, 0xff, 0xff, 0xff, 0xff, 0x10, 0]), 2),
+            Err(InvalidAdjacency::Varints { node: 0 })
+        );
+        assert_eq!(validate(&compressed(vec![0, 2, 3], 1, vec![1, 2, 0]), 2), Err(InvalidAdjacency::Neighbour { node: 0, neighbour: 2 }));
+        assert_eq!(validate(&compressed(vec![0, 2, 3], 2, vec![1, 1, 0]), 2), Err(InvalidAdjacency::EdgeCount { expected: 2, found: 1 }));
+    }
+}
@@ -0,0 +1,71 @@
+//! Rewrite a graph file built by an older sql-dump-to-rust in the current
+//! format, without rebuilding it from the dumps.
+//!
+//! Usage: `migrate-graph [--compress] <input> [output]`, the input is
+//! replaced when no output is given. `--compress` also switches the links to
+//! the compressed encoding.
+
+use memmap2::Mmap;
+use rust_serverless::graph::GRAPH_FORMAT_VERSION;
+use rust_serverless::migrate::{format_version, graph_file_bytes, read_graph};
+use std::fs::File;
+use std::path::PathBuf;
+use std::process::exit;
+
+fn main() {
+    let mut compress = false;
+    let mut paths = Vec::new();
+    for arg in std::env::args_os().skip(1) {
+        if arg == "--compress" {
+            compress = true;
+        } else {
+            paths.push(PathBuf::from(arg));
+        }
+    }
+    let Some(input) = paths.first().cloned() else {
+        eprintln!("Usage: migrate-graph [--compress] <input> [output]");
+        exit(2);
+    };
+    let output = paths.get(1).cloned().unwrap_or_else(|| input.clone());
+
+    let file = File::open(&input).unwrap_or_else(|err| {
+        eprintln!("Failed to open {}: {}", input.display(), err);
+        exit(1);
+    });
+    // SAFETY: The file is not modified while it is mapped, the output is written to a temporary file.
+    let mmap = unsafe { Mmap::map(&file) }.unwrap_or_else(|err| {
+        eprintln!("Failed to memory-map {}: {}", input.display(), err);
+        exit(1);
+    });
+
+    if format_version(&mmap) == Some(GRAPH_FORMAT_VERSION) && !compress && input == output {
+        println!("{} already has format version {}", input.display(), GRAPH_FORMAT_VERSION);
+        return;
+    }
+
+    let mut graph = read_graph(&mmap).unwrap_or_else(|err| {
+        eprintln!("Failed to migrate {}: {}", input.display(), err);
+        exit(1);
+    });
+    if compress {
+        graph.links = graph.links.compress();
+        graph.reverse_links = graph.reverse_links.compress();
+    }
+    let bytes = graph_file_bytes(&graph).expect("Graph RKYV serialization failed");
+
+    let mut temporary = output.clone().into_os_string();
+    temporary.push(".migrating");
+    let temporary = PathBuf::from(temporary);
+    if let Err(err) = std::fs::write(&temporary, &bytes).and_then(|_| std::fs::rename(&temporary, &output)) {
+        eprintln!("Failed to write {}: {}", output.display(), err);
+        exit(1);
+    }
+    println!(
+        "Migrated {} to format version {}{}: {} -> {} bytes",
+        output.display(),
+        GRAPH_FORMAT_VERSION,
+        if compress { " with compressed links" } else { "" },
+        mmap.len(),
+        bytes.len()
+    );
+}
@@ -0,0 +1,155 @@
+use rkyv::rend::u32_le;
+use rkyv::{Archive, Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::adjacency::Adjacency;
+
+/// Bumped whenever the archived layout of `CsrGraph` changes.
+pub const GRAPH_FORMAT_VERSION: u32 = 4;
+/// Start of every graph file, followed by the format version.
+pub const GRAPH_MAGIC: [u8; 8] = *b"WIKIGRPH";
+/// Bytes before the archive: magic, format version, then padding keeping the archive 16-byte aligned.
+pub const GRAPH_HEADER_LEN: usize = 16;
+
+/// Where a graph comes from, written by sql-dump-to-rust.
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+pub struct GraphMeta {
+    pub format_version: u32,
+    pub lang: String,
+    pub dumps: Vec<DumpMeta>,
+    // sql-dump-to-rust version and git revision that built the graph.
+    pub builder_version: String,
+    // Unix timestamp in seconds.
+    pub built_at: u64,
+    pub build_duration_ms: u64,
+    pub node_count: u32,
+    pub edge_count: u64,
+}
+
+/// Which dump file a graph was built from.
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+pub struct DumpMeta {
+    pub file_type: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+pub struct CsrGraph {
+    pub meta: GraphMeta,
+    // Outgoing links of every node, and incoming links for the backward search.
+    pub links: Adjacency,
+    pub reverse_links: Adjacency,
+    // Page id of every node.
+    pub page_ids: Vec<u32>,
+    // Nodes sorted by page id when they were reordered for locality, empty
+    // when `page_ids` is itself sorted.
+    pub sorted_nodes: Vec<u32>,
+    // Title lookup tables so the server can resolve titles (and redirects) to page ids.
+    pub pages: HashMap<String, WikiPageId>,
+    pub redirects: HashMap<u32, String>,
+    // Page title of every node, indexed by node.
+    pub titles: Vec<String>,
+    // Index of every non redirect node, sorted by title for prefix search.
+    pub title_index: Vec<u32>,
+}
+
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct WikiPageId {
+    pub id: u32,
+    pub is_redirect: bool,
+}
+
+// Upper bound on the matches scanned when ranking, so short prefixes stay cheap.
+const SEARCH_MAX_RANKED_CANDIDATES: usize = 1 << 20;
+
+/// Turn a user supplied title into the form used by the dump: underscores
+/// instead of spaces and an upper-cased first letter.
+pub fn normalize_title(title: &str) -> String {
+    let title = title.trim().replace(' ', "_");
+    let mut chars = title.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => title,
+    }
+}
+
+/// Resolve a page title to the page id of the article it leads to, following
+/// redirects the same way `resolve_redirect` does in sql-dump-to-rust.
+pub fn resolve_title(graph: &ArchivedCsrGraph, title: &str) -> Option<u32> {
+    let page = graph.pages.get(title)
+        .or_else(|| graph.pages.get(normalize_title(title).as_str()))?;
+    if !page.is_redirect {
+        return Some(page.id.to_native());
+    }
+
+    let mut n: u8 = 0;
+    let mut last_title = graph.redirects.get(&page.id);
+    while n < 10 {
+        let page = graph.pages.get(last_title?.as_str())?;
+        if !page.is_redirect {
+            return Some(page.id.to_native());
+        }
+        last_title = graph.redirects.get(&page.id);
+        n += 1;
+    }
+    None
+}
+
+/// Node index of a page, by binary search in the page ids.
+pub fn page_index(graph: &ArchivedCsrGraph, page_id: u32) -> Option<u32> {
+    if graph.sorted_nodes.is_empty() {
+        return graph.page_ids.binary_search_by_key(&page_id, |id| id.to_native()).ok().map(|index| index as u32);
+    }
+    let position = graph.sorted_nodes
+        .binary_search_by_key(&page_id, |node| graph.page_ids[node.to_native() as usize].to_native())
+        .ok()?;
+    Some(graph.sorted_nodes[position].to_native())
+}
+
+/// Page id of a node.
+pub fn page_id(graph: &ArchivedCsrGraph, index: u32) -> u32 {
+    graph.page_ids[index as usize].to_native()
+}
+
+/// Title of a page from the index aligned title table.
+pub fn page_title(graph: &ArchivedCsrGraph, page_id: u32) -> Option<&str> {
+    let index = page_index(graph, page_id)?;
+    graph.titles.get(index as usize).map(|title| title.as_str())
+}
+
+/// Number of links leading to a node.
+pub fn in_degree(graph: &ArchivedCsrGraph, index: u32) -> u32 {
+    graph.reverse_links.degree(index) as u32
+}
+
+/// Node indices whose title starts with `prefix`, in title order or by
+/// decreasing in-degree when `rank_by_in_degree` is set.
+pub fn search_titles(graph: &ArchivedCsrGraph, prefix: &str, limit: usize, rank_by_in_degree: bool) -> Vec<u32> {
+    let title_index = graph.title_index.as_slice();
+    let title = |index: &u32_le| graph.titles[index.to_native() as usize].as_str();
+
+    let start = title_index.partition_point(|index| title(index) < prefix);
+    let len = title_index[start..].partition_point(|index| title(index).starts_with(prefix));
+    let matches = &title_index[start..start + len];
+
+    if !rank_by_in_degree {
+        return matches.iter().take(limit).map(|index| index.to_native()).collect();
+    }
+
+    // Ties keep title order, whatever the node order of the graph.
+    let mut candidates: Vec<(u32, u32)> = matches
+        .iter()
+        .take(SEARCH_MAX_RANKED_CANDIDATES)
+        .enumerate()
+        .map(|(position, index)| (in_degree(graph, index.to_native()), position as u32))
+        .collect();
+    let by_rank = |a: &(u32, u32), b: &(u32, u32)| b.0.cmp(&a.0).then(a.1.cmp(&b.1));
+    if candidates.len() > limit && limit > 0 {
+        candidates.select_nth_unstable_by(limit - 1, by_rank);
+    }
+    candidates.truncate(limit);
+    candidates.sort_unstable_by(by_rank);
+    candidates.into_iter().map(|(_, position)| matches[position as usize].to_native()).collect()
+}
+
@@ -0,0 +1,6 @@
+pub mod adjacency;
+pub mod graph;
+pub mod shortest_path;
+pub mod workspace;
+pub mod loader;
+pub mod migrate;
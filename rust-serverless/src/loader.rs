@@ -0,0 +1,135 @@
+use memmap2::Mmap;
+use rkyv::{access, access_unchecked, rancor};
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::adjacency::InvalidAdjacency;
+use crate::graph::{ArchivedCsrGraph, GRAPH_FORMAT_VERSION, GRAPH_HEADER_LEN};
+use crate::migrate::format_version;
+
+/// Why `graph.rkyv` could not be turned into a usable graph.
+#[derive(Debug)]
+pub enum GraphLoadError {
+    /// The graph file could not be opened.
+    Open { path: PathBuf, source: io::Error },
+    /// The graph file could not be memory-mapped.
+    Map { path: PathBuf, source: io::Error },
+    /// The file does not start with the graph header, it is not a graph or
+    /// was built before graphs were versioned.
+    UnknownFormat { path: PathBuf },
+    /// The graph was built for another format version.
+    UnsupportedVersion { path: PathBuf, version: u32 },
+    /// The bytes are not a valid archived `CsrGraph`, either a truncated
+    /// file or one written by an incompatible sql-dump-to-rust.
+    Validate { path: PathBuf, source: rancor::Error },
+    /// The archive is valid but the links of one direction are corrupt.
+    InvalidLinks { path: PathBuf, direction: &'static str, source: InvalidAdjacency },
+}
+
+impl fmt::Display for GraphLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphLoadError::Open { path, source } if source.kind() == io::ErrorKind::NotFound => write!(
+                f,
+                "graph file {} does not exist, set GRAPH_PATH or pass --graph <path>",
+                path.display()
+            ),
+            GraphLoadError::Open { path, source } => write!(f, "failed to open graph file {}: {}", path.display(), source),
+            GraphLoadError::Map { path, source } => write!(f, "failed to memory-map graph file {}: {}", path.display(), source),
+            GraphLoadError::UnknownFormat { path } => write!(
+                f,
+                "graph file {} has no graph header, rebuild it with a recent sql-dump-to-rust",
+                path.display()
+            ),
+            GraphLoadError::UnsupportedVersion { path, version } if *version < GRAPH_FORMAT_VERSION => write!(
+                f,
+                "graph file {} has format version {} but version {} is expected, convert it with `migrate-graph {}`",
+                path.display(),
+                version,
+                GRAPH_FORMAT_VERSION,
+                path.display()
+            ),
+            GraphLoadError::UnsupportedVersion { path, version } => write!(
+                f,
+                "graph file {} has format version {} but version {} is expected, rebuild it with a matching sql-dump-to-rust",
+                path.display(),
+                version,
+                GRAPH_FORMAT_VERSION
+            ),
+            GraphLoadError::Validate { path, source } => write!(
+                f,
+                "graph file {} is not a valid archived graph (truncated or built by an incompatible sql-dump-to-rust): {}",
+                path.display(),
+                source
+            ),
+            GraphLoadError::InvalidLinks { path, direction, source } => write!(
+                f,
+                "graph file {} has corrupt {} links, rebuild it: {}",
+                path.display(),
+                direction,
+                source
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GraphLoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GraphLoadError::Open { source, .. } | GraphLoadError::Map { source, .. } => Some(source),
+            GraphLoadError::Validate { source, .. } => Some(source),
+            GraphLoadError::InvalidLinks { source, .. } => Some(source),
+            GraphLoadError::UnknownFormat { .. } | GraphLoadError::UnsupportedVersion { .. } => None,
+        }
+    }
+}
+
+/// A memory-mapped graph file whose archive has been validated.
+///
+/// Owning the mapping, instead of leaking it, lets a replaced graph be
+/// unmapped once the last request holding it is done.
+pub struct LoadedGraph {
+    path: PathBuf,
+    mmap: Mmap,
+}
+
+impl LoadedGraph {
+    /// Memory-map and validate the archived graph at `path`.
+    pub fn load(path: &Path) -> Result<Self, GraphLoadError> {
+        let file = File::open(path).map_err(|source| GraphLoadError::Open { path: path.to_owned(), source })?;
+        // SAFETY: Writers never modify a graph file in place, they write a new
+        // file and rename it over the old one, so this mapping stays unchanged.
+        let mmap = unsafe { Mmap::map(&file) }.map_err(|source| GraphLoadError::Map { path: path.to_owned(), source })?;
+
+        let Some(version) = format_version(&mmap) else {
+            return Err(GraphLoadError::UnknownFormat { path: path.to_owned() });
+        };
+        if version != GRAPH_FORMAT_VERSION {
+            return Err(GraphLoadError::UnsupportedVersion { path: path.to_owned(), version });
+        }
+
+        let graph = access::<ArchivedCsrGraph, rancor::Error>(&mmap[GRAPH_HEADER_LEN..])
+            .map_err(|source| GraphLoadError::Validate { path: path.to_owned(), source })?;
+        // Searches decode the links as if they were well formed, so a corrupt
+        // file is rejected here rather than panicking in a query.
+        let node_count = graph.page_ids.len() as u32;
+        for (direction, links) in [("outgoing", &graph.links), ("incoming", &graph.reverse_links)] {
+            links
+                .validate(node_count)
+                .map_err(|source| GraphLoadError::InvalidLinks { path: path.to_owned(), direction, source })?;
+        }
+
+        Ok(Self { path: path.to_owned(), mmap })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn graph(&self) -> &ArchivedCsrGraph {
+        // SAFETY: The bytes were validated in `load` and the mapping is never written to.
+        unsafe { access_unchecked::<ArchivedCsrGraph>(&self.mmap[GRAPH_HEADER_LEN..]) }
+    }
+}
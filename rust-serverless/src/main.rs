@@ -1,251 +1,458 @@
-use actix_web::{get, web, App, HttpServer, Responder, HttpResponse};
+use actix_web::{get, post, web, App, FromRequest, HttpRequest, HttpServer, Responder, HttpResponse, ResponseError};
+use actix_web::dev::Payload;
+use actix_web::http::{header, StatusCode};
 use actix_cors::Cors;
-use env_logger;
-use memmap2::Mmap;
-use once_cell::sync::Lazy; // Import Lazy
-use rayon::prelude::*;
-use rkyv::rend::u32_le;
-use rkyv::{access, rancor, Archive, Deserialize, Serialize};
-use rustc_hash::{FxBuildHasher, FxHashMap, FxHashSet};
-use std::collections::HashMap;
-use std::fs::File;
-
-#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
-struct CsrGraph {
-    offsets: Vec<u32>,
-    edges: Vec<u32>,
-    reverse_offsets: Vec<u32>,
-    reverse_edges: Vec<u32>,
-    page_id_to_index: HashMap<u32, u32>,
-    index_to_page_id: HashMap<u32, u32>,
+use arc_swap::ArcSwapOption;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Ready;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
+
+use rust_serverless::graph::{normalize_title, page_id, page_title, resolve_title, search_titles, in_degree, ArchivedCsrGraph};
+use rust_serverless::loader::{GraphLoadError, LoadedGraph};
+use rust_serverless::shortest_path::{count_shortest_path, find_all_shortest_path, find_shortest_path};
+
+const DEFAULT_GRAPH_PATH: &str = "graph.rkyv";
+const DEFAULT_GRAPHS_DIR: &str = "graphs";
+// Graphs found in a graphs directory are named like the dumps, `enwiki.rkyv`.
+const GRAPH_FILE_SUFFIX: &str = "wiki.rkyv";
+
+/// The graph served for one language.
+struct GraphSlot {
+    path: PathBuf,
+    // Empty until the first load finishes, requests fail with 503 until then.
+    // Each request keeps the graph it started with even if a reload swaps it.
+    graph: ArcSwapOption<LoadedGraph>,
+    // Held while a graph is being loaded so reloads never overlap.
+    loading: Mutex<()>,
 }
 
-// Define a global static variable for the graph.
-// It will be initialized exactly once, on the first time it's accessed.
-static GRAPH: Lazy<&'static ArchivedCsrGraph> = Lazy::new(|| {
-    log::info!("Lazily loading graph data for static access...");
+impl GraphSlot {
+    fn new(path: PathBuf) -> Self {
+        Self { path, graph: ArcSwapOption::empty(), loading: Mutex::new(()) }
+    }
 
-    let file = File::open("graph.rkyv").expect("Failed to open graph.rkyv");
-    // SAFETY: The file is trusted and not modified elsewhere.
-    let mmap = unsafe { Mmap::map(&file).expect("Failed to memory-map the file.") };
+    /// The file of the graph being served, or the configured one before the first load.
+    fn current_graph_path(&self) -> PathBuf {
+        self.graph.load().as_ref().map_or_else(|| self.path.clone(), |loaded| loaded.path().to_owned())
+    }
 
-    // Leak the mmap to get a 'static lifetime.
-    let mmap_static: &'static [u8] = Box::leak(Box::new(mmap));
+    /// Load and validate the graph at `path` then swap it in. Requests already
+    /// running keep the previous graph, which is unmapped once they finish.
+    fn load_graph(&self, lang: &str, path: &Path) -> Result<Arc<LoadedGraph>, GraphLoadError> {
+        let _loading = self.loading.lock().unwrap();
+        log::info!("[{}] Loading graph from {}...", lang, path.display());
+        let start_time = std::time::Instant::now();
+        let loaded = Arc::new(LoadedGraph::load(path)?);
+        let graph = loaded.graph();
+        log::info!(
+            "[{}] Graph data loaded and ready in {:.2} s: {} nodes, {} edges, {} titles, built by {} at {}",
+            lang,
+            start_time.elapsed().as_secs_f64(),
+            graph.links.node_count(),
+            graph.links.edge_count(),
+            graph.titles.len(),
+            graph.meta.builder_version,
+            graph.meta.built_at
+        );
+        for dump in graph.meta.dumps.iter() {
+            log::info!(
+                "[{}] Built from {} dump, last modified {}",
+                lang,
+                dump.file_type,
+                dump.last_modified.as_ref().map_or("unknown", |last_modified| last_modified.as_str())
+            );
+        }
+        if graph.meta.lang != lang {
+            log::warn!("[{}] Graph file {} was built for language {}", lang, path.display(), graph.meta.lang);
+        }
+        self.graph.store(Some(Arc::clone(&loaded)));
+        Ok(loaded)
+    }
+}
 
-    // Use `access` for validation. Panics on failure, which is common for lazy_static.
-    // For production, you might want more graceful error handling.
-    access::<ArchivedCsrGraph, rancor::Error>(mmap_static).expect("Failed to validate archived graph.")
-});
+struct AppState {
+    graphs: BTreeMap<String, GraphSlot>,
+    // Language served by the routes without a `/{lang}` prefix.
+    default_lang: String,
+    // Admin endpoints are disabled when no ADMIN_TOKEN is configured.
+    admin_token: Option<String>,
+}
 
+impl AppState {
+    /// Language of the request, from its `/{lang}` prefix or the default one.
+    fn lang<'a>(&'a self, request: &'a HttpRequest) -> &'a str {
+        request.match_info().get("lang").unwrap_or(&self.default_lang)
+    }
 
-struct AppState {
-    graph: &'static ArchivedCsrGraph,
+    fn slot(&self, lang: &str) -> Result<&GraphSlot, GraphUnavailable> {
+        self.graphs.get(lang).ok_or_else(|| GraphUnavailable::UnknownLang(lang.to_owned()))
+    }
+
+    fn graph(&self, lang: &str) -> Result<Arc<LoadedGraph>, GraphUnavailable> {
+        self.slot(lang)?.graph.load_full().ok_or_else(|| GraphUnavailable::Loading(lang.to_owned()))
+    }
+}
+
+#[derive(Debug)]
+enum GraphUnavailable {
+    UnknownLang(String),
+    Loading(String),
 }
 
-fn reconstruct_paths(
-    node: u32,
-    start_node: u32,
-    parents: &FxHashMap<u32, Vec<u32>>,
-) -> Vec<Vec<u32>> {
-    if node == start_node {
-        return vec![vec![start_node]];
+impl fmt::Display for GraphUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphUnavailable::UnknownLang(lang) => write!(f, "No graph is served for language {}", lang),
+            GraphUnavailable::Loading(lang) => write!(f, "Graph for language {} is still loading", lang),
+        }
     }
+}
 
-    let mut paths = Vec::new();
-    if let Some(parent_nodes) = parents.get(&node) {
-        for &parent_node in parent_nodes {
-            let parent_paths = reconstruct_paths(parent_node, start_node, parents);
-            for mut path in parent_paths {
-                path.push(node);
-                paths.push(path);
-            }
+impl ResponseError for GraphUnavailable {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            GraphUnavailable::UnknownLang(_) => StatusCode::NOT_FOUND,
+            GraphUnavailable::Loading(_) => StatusCode::SERVICE_UNAVAILABLE,
         }
     }
-    paths
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(serde_json::json!({ "error": self.to_string() }))
+    }
 }
 
-fn find_all_shortest_path(
-    graph: &'static ArchivedCsrGraph,
-    start_page_id: u32,
-    end_page_id: u32,
-) -> Vec<Vec<u32>> {
-    let start_node = match graph.page_id_to_index.get(&u32_le::from_native(start_page_id)) {
-        Some(id) => id.to_native(),
-        None => return vec![],
-    };
-    let end_node = match graph.page_id_to_index.get(&u32_le::from_native(end_page_id)) {
-        Some(id) => id.to_native(),
-        None => return vec![],
-    };
+/// The graph of the language the request is routed to.
+struct LangGraph(Arc<LoadedGraph>);
 
-    if start_node == end_node {
-        return vec![vec![start_page_id]];
+impl FromRequest for LangGraph {
+    type Error = GraphUnavailable;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(request: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let state = request.app_data::<web::Data<AppState>>().expect("AppState is registered");
+        std::future::ready(state.graph(state.lang(request)).map(LangGraph))
     }
+}
 
-    // Use HashSets for frontiers for efficient lookups and to represent levels.
-    let mut forward_frontier = FxHashSet::with_hasher(FxBuildHasher::default());
-    forward_frontier.insert(start_node);
-    let mut backward_frontier = FxHashSet::with_hasher(FxBuildHasher::default());
-    backward_frontier.insert(end_node);
-
-    // Visited maps store distances and parents.
-    let mut forward_dist: FxHashMap<u32, u32> = FxHashMap::with_hasher(FxBuildHasher::default());
-    forward_dist.insert(start_node, 0);
-    let mut backward_dist: FxHashMap<u32, u32> = FxHashMap::with_hasher(FxBuildHasher::default());
-    backward_dist.insert(end_node, 0);
-
-    let mut forward_parents: FxHashMap<u32, Vec<u32>> = FxHashMap::with_hasher(FxBuildHasher::default());
-    let mut backward_parents: FxHashMap<u32, Vec<u32>> = FxHashMap::with_hasher(FxBuildHasher::default());
-
-    let mut meeting_nodes = FxHashSet::with_hasher(FxBuildHasher::default());
-    let mut shortest_path_len = u32::MAX;
-    let mut forward_depth = 0;
-    let mut backward_depth = 0;
-
-    while !forward_frontier.is_empty() && !backward_frontier.is_empty() {
-        // Stop if we can't find a shorter path than we've already found.
-        if forward_depth + backward_depth >= shortest_path_len {
-            break;
+/// Graphs to serve, by language, in the order they were configured.
+///
+/// `--graph [lang=]path` may be repeated, or `GRAPH_PATH` hold a comma
+/// separated list of the same entries. Without either, every `{lang}wiki.rkyv`
+/// in `--graphs-dir`, `GRAPHS_DIR` or `graphs/` is served, falling back to
+/// `graph.rkyv`.
+fn graph_paths() -> Vec<(String, PathBuf)> {
+    let mut graph_args = Vec::new();
+    let mut graphs_dir = std::env::var("GRAPHS_DIR").ok().map(PathBuf::from);
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--graph" {
+            graph_args.extend(args.next());
+        } else if let Some(graph) = arg.strip_prefix("--graph=") {
+            graph_args.push(graph.to_owned());
+        } else if arg == "--graphs-dir" {
+            graphs_dir = args.next().map(PathBuf::from);
+        } else if let Some(dir) = arg.strip_prefix("--graphs-dir=") {
+            graphs_dir = Some(PathBuf::from(dir));
         }
-
-        // Python script trick: expand the frontier with fewer outgoing links.
-        let forward_link_count: usize = forward_frontier.par_iter().map(|&u| {
-            let start = graph.offsets[u as usize].to_native() as usize;
-            let end = graph.offsets[(u + 1) as usize].to_native() as usize;
-            end - start
-        }).sum();
-        let backward_link_count: usize = backward_frontier.par_iter().map(|&u| {
-            let start = graph.reverse_offsets[u as usize].to_native() as usize;
-            let end = graph.reverse_offsets[(u + 1) as usize].to_native() as usize;
-            end - start
-        }).sum();
-
-        let expand_forward = forward_link_count <= backward_link_count;
-
-        if expand_forward {
-            forward_depth += 1;
-            let mut next_frontier = FxHashSet::with_capacity_and_hasher(forward_frontier.len() * 5, FxBuildHasher::default());
-            for &u in &forward_frontier {
-                let start_offset = graph.offsets[u as usize].to_native() as usize;
-                let end_offset = graph.offsets[(u + 1) as usize].to_native() as usize;
-                for v_le in &graph.edges[start_offset..end_offset] {
-                    let v = v_le.to_native();
-
-                    if !forward_dist.contains_key(&v) {
-                        forward_dist.insert(v, forward_depth);
-                        forward_parents.insert(v, vec![u]);
-                        next_frontier.insert(v);
-                    } else if forward_dist[&v] == forward_depth {
-                        forward_parents.get_mut(&v).unwrap().push(u);
-                    }
-                }
-            }
-            forward_frontier = next_frontier;
-
-            // Check for intersections with the backward search's visited nodes.
-            for &node in &forward_frontier {
-                if let Some(&bwd_dist) = backward_dist.get(&node) {
-                    let path_len = forward_depth + bwd_dist;
-                    if path_len < shortest_path_len {
-                        shortest_path_len = path_len;
-                        meeting_nodes.clear();
-                        meeting_nodes.insert(node);
-                    } else if path_len == shortest_path_len {
-                        meeting_nodes.insert(node);
-                    }
-                }
-            }
-        } else { // Expand backward
-            backward_depth += 1;
-            let mut next_frontier = FxHashSet::with_capacity_and_hasher(backward_frontier.len() * 5, FxBuildHasher::default());
-            for &u in &backward_frontier {
-                let start_offset = graph.reverse_offsets[u as usize].to_native() as usize;
-                let end_offset = graph.reverse_offsets[(u + 1) as usize].to_native() as usize;
-                for v_le in &graph.reverse_edges[start_offset..end_offset] {
-                    let v = v_le.to_native();
-
-                    if !backward_dist.contains_key(&v) {
-                        backward_dist.insert(v, backward_depth);
-                        backward_parents.insert(v, vec![u]);
-                        next_frontier.insert(v);
-                    } else if backward_dist[&v] == backward_depth {
-                        backward_parents.get_mut(&v).unwrap().push(u);
-                    }
-                }
-            }
-            backward_frontier = next_frontier;
-
-            // Check for intersections with the forward search's visited nodes.
-            for &node in &backward_frontier {
-                if let Some(&fwd_dist) = forward_dist.get(&node) {
-                    let path_len = backward_depth + fwd_dist;
-                    if path_len < shortest_path_len {
-                        shortest_path_len = path_len;
-                        meeting_nodes.clear();
-                        meeting_nodes.insert(node);
-                    } else if path_len == shortest_path_len {
-                        meeting_nodes.insert(node);
-                    }
-                }
-            }
+    }
+    if graph_args.is_empty() {
+        if let Ok(graph_path) = std::env::var("GRAPH_PATH") {
+            graph_args = graph_path.split(',').map(str::to_owned).collect();
         }
     }
+    if !graph_args.is_empty() {
+        return graph_args.iter().map(|graph| parse_graph_arg(graph)).collect();
+    }
+
+    let graphs_dir = graphs_dir.unwrap_or_else(|| PathBuf::from(DEFAULT_GRAPHS_DIR));
+    let mut graphs: Vec<(String, PathBuf)> = std::fs::read_dir(&graphs_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let lang = path.file_name()?.to_str()?.strip_suffix(GRAPH_FILE_SUFFIX)?.to_owned();
+            Some((lang, path))
+        })
+        .collect();
+    graphs.sort();
+    if graphs.is_empty() {
+        graphs.push(parse_graph_arg(DEFAULT_GRAPH_PATH));
+    }
+    graphs
+}
 
-    if meeting_nodes.is_empty() {
-        return vec![];
+/// Split `lang=path`. A bare path serves `WIKI_LANG`, or the language in a
+/// `{lang}wiki.rkyv` file name, or `en`.
+fn parse_graph_arg(graph: &str) -> (String, PathBuf) {
+    if let Some((lang, path)) = graph.split_once('=') {
+        return (lang.to_owned(), PathBuf::from(path));
     }
+    let path = PathBuf::from(graph);
+    let lang = std::env::var("WIKI_LANG").ok().or_else(|| {
+        Some(path.file_name()?.to_str()?.strip_suffix(GRAPH_FILE_SUFFIX)?.to_owned())
+    });
+    (lang.unwrap_or_else(|| "en".to_owned()), path)
+}
 
-    let all_paths: FxHashSet<Vec<u32>> = meeting_nodes
-        .par_iter()
-        .flat_map(|&meet_node| {
-            let forward_paths = reconstruct_paths(meet_node, start_node, &forward_parents);
-            let backward_paths = reconstruct_paths(meet_node, end_node, &backward_parents);
-
-            let mut combined_paths = Vec::with_capacity(forward_paths.len() * backward_paths.len());
-
-            for f_path in &forward_paths {
-                for b_path in &backward_paths {
-                    let mut path = f_path.clone();
-                    let mut reversed_b_path = b_path.clone();
-                    reversed_b_path.reverse();
-                    path.extend_from_slice(&reversed_b_path[1..]);
-                    combined_paths.push(path);
-                }
+#[get("/health")]
+async fn health() -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({ "status": "ok" }))
+}
+
+/// Ready once the graph of every language is loaded.
+#[get("/ready")]
+async fn ready(state: web::Data<AppState>) -> impl Responder {
+    let mut all_ready = true;
+    let graphs: serde_json::Map<String, serde_json::Value> = state.graphs.iter().map(|(lang, slot)| {
+        let status = match slot.graph.load_full() {
+            Some(loaded) => serde_json::json!({
+                "status": "ready",
+                "graph_path": loaded.path(),
+                "nodes": loaded.graph().links.node_count(),
+                "edges": loaded.graph().links.edge_count(),
+            }),
+            None => {
+                all_ready = false;
+                serde_json::json!({
+                    "status": "loading",
+                    "graph_path": slot.path,
+                })
             }
-            combined_paths
-        })
+        };
+        (lang.clone(), status)
+    }).collect();
+
+    let response = serde_json::json!({
+        "status": if all_ready { "ready" } else { "loading" },
+        "default_lang": state.default_lang,
+        "graphs": graphs,
+    });
+    if all_ready {
+        HttpResponse::Ok().json(response)
+    } else {
+        HttpResponse::ServiceUnavailable().json(response)
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct ReloadQuery {
+    /// Graph file to load, the one currently served when absent.
+    path: Option<PathBuf>,
+}
+
+/// Swap in a new graph for the routed language without restarting,
+/// authenticated with `Authorization: Bearer <ADMIN_TOKEN>`.
+#[post("/admin/reload")]
+async fn admin_reload(
+    state: web::Data<AppState>,
+    request: HttpRequest,
+    query: web::Query<ReloadQuery>,
+) -> impl Responder {
+    let Some(admin_token) = &state.admin_token else {
+        return HttpResponse::NotFound().json(serde_json::json!({ "error": "Admin endpoints are disabled, set ADMIN_TOKEN to enable them" }));
+    };
+    let authorization = request.headers().get(header::AUTHORIZATION).and_then(|value| value.to_str().ok());
+    if authorization != Some(format!("Bearer {}", admin_token).as_str()) {
+        return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Invalid admin token" }));
+    }
+
+    let lang = state.lang(&request).to_owned();
+    let slot = match state.slot(&lang) {
+        Ok(slot) => slot,
+        Err(err) => return err.error_response(),
+    };
+    let path = query.into_inner().path.unwrap_or_else(|| slot.current_graph_path());
+    let loading_state = state.clone();
+    let loading_lang = lang.clone();
+    let loading_path = path.clone();
+    let reloaded = web::block(move || {
+        loading_state.graphs[&loading_lang].load_graph(&loading_lang, &loading_path)
+    })
+    .await
+    .unwrap();
+    match reloaded {
+        Ok(loaded) => HttpResponse::Ok().json(serde_json::json!({
+            "status": "reloaded",
+            "lang": lang,
+            "graph_path": loaded.path(),
+            "nodes": loaded.graph().links.node_count(),
+            "edges": loaded.graph().links.edge_count(),
+        })),
+        Err(err) => {
+            log::error!("[{}] Failed to reload graph, still serving the previous one: {}", lang, err);
+            HttpResponse::UnprocessableEntity().json(serde_json::json!({
+                "error": err.to_string(),
+                "graph_path": path,
+            }))
+        }
+    }
+}
+
+/// Where the served graph comes from: language, dumps and builder.
+#[get("/meta")]
+async fn meta(LangGraph(loaded): LangGraph) -> HttpResponse {
+    let meta = &loaded.graph().meta;
+    let dumps: Vec<serde_json::Value> = meta.dumps.iter().map(|dump| serde_json::json!({
+        "file_type": dump.file_type.as_str(),
+        "etag": dump.etag.as_ref().map(|etag| etag.as_str()),
+        "last_modified": dump.last_modified.as_ref().map(|last_modified| last_modified.as_str()),
+    })).collect();
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "graph_path": loaded.path(),
+        "format_version": meta.format_version.to_native(),
+        "lang": meta.lang.as_str(),
+        "dumps": dumps,
+        "builder_version": meta.builder_version.as_str(),
+        "built_at": meta.built_at.to_native(),
+        "build_duration_ms": meta.build_duration_ms.to_native(),
+        "node_count": meta.node_count.to_native(),
+        "edge_count": meta.edge_count.to_native(),
+        "compressed": loaded.graph().links.is_compressed(),
+    }))
+}
+
+const SEARCH_DEFAULT_LIMIT: usize = 10;
+const SEARCH_MAX_LIMIT: usize = 100;
+
+#[derive(serde::Deserialize)]
+struct SearchQuery {
+    prefix: String,
+    limit: Option<usize>,
+    rank: Option<bool>,
+}
+
+#[get("/search")]
+async fn search(
+    LangGraph(loaded): LangGraph,
+    query: web::Query<SearchQuery>,
+) -> HttpResponse {
+    let graph = loaded.graph();
+    let query = query.into_inner();
+    let prefix = normalize_title(&query.prefix);
+    let limit = query.limit.unwrap_or(SEARCH_DEFAULT_LIMIT).min(SEARCH_MAX_LIMIT);
+    let rank_by_in_degree = query.rank.unwrap_or(false);
+
+    let start_time = std::time::Instant::now();
+
+    let results: Vec<serde_json::Value> = search_titles(graph, &prefix, limit, rank_by_in_degree)
+        .into_iter()
+        .map(|index| serde_json::json!({
+            "id": page_id(graph, index),
+            "title": graph.titles[index as usize].as_str(),
+            "in_degree": in_degree(graph, index),
+        }))
         .collect();
 
-    all_paths.into_par_iter().map(|path| {
-        path.into_iter().map(|idx| graph.index_to_page_id.get(&u32_le::from_native(idx)).unwrap().to_native()).collect()
-    }).collect()
+    let elapsed_time = start_time.elapsed();
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "results": results,
+        "time_spent_ms": elapsed_time.as_millis()
+    }))
+}
+
+/// A path of page ids, or of `{id, title}` objects when `with_titles` is set.
+fn path_json(graph: &ArchivedCsrGraph, path: &[u32], with_titles: bool) -> serde_json::Value {
+    if !with_titles {
+        return serde_json::json!(path);
+    }
+    serde_json::json!(path.iter().map(|&page_id| serde_json::json!({
+        "id": page_id,
+        "title": page_title(graph, page_id),
+    })).collect::<Vec<_>>())
+}
+
+#[derive(serde::Deserialize)]
+struct PageIds {
+    from_page_id: u32,
+    to_page_id: u32,
+}
+
+#[derive(serde::Deserialize)]
+struct PageTitles {
+    from_title: String,
+    to_title: String,
+}
+
+#[derive(serde::Deserialize)]
+struct PathQuery {
+    with_titles: Option<bool>,
+    /// Maximum number of paths to return, all of them when absent.
+    limit: Option<usize>,
 }
 
 #[get("/all-shortest-path/{from_page_id}/to/{to_page_id}")]
 async fn all_shortest_path(
-    state: web::Data<AppState>,
-    path_params: web::Path<(u32, u32)>,
-) -> impl Responder {
-    let (from_page_id, to_page_id) = path_params.into_inner();
+    LangGraph(loaded): LangGraph,
+    path_params: web::Path<PageIds>,
+    query: web::Query<PathQuery>,
+) -> HttpResponse {
+    let PageIds { from_page_id, to_page_id } = path_params.into_inner();
+    let with_titles = query.with_titles.unwrap_or(false);
+    all_shortest_path_response(loaded, from_page_id, to_page_id, with_titles, query.limit).await
+}
 
-    let graph = state.graph;
+#[get("/all-shortest-path/by-title/{from_title}/to/{to_title}")]
+async fn all_shortest_path_by_title(
+    LangGraph(loaded): LangGraph,
+    path_params: web::Path<PageTitles>,
+    query: web::Query<PathQuery>,
+) -> HttpResponse {
+    let PageTitles { from_title, to_title } = path_params.into_inner();
+    let with_titles = query.with_titles.unwrap_or(false);
+    let graph = loaded.graph();
+
+    let Some(from_page_id) = resolve_title(graph, &from_title) else {
+        return HttpResponse::NotFound().json(serde_json::json!({
+            "error": format!("No page found for title {}", from_title)
+        }));
+    };
+    let Some(to_page_id) = resolve_title(graph, &to_title) else {
+        return HttpResponse::NotFound().json(serde_json::json!({
+            "error": format!("No page found for title {}", to_title)
+        }));
+    };
+
+    all_shortest_path_response(loaded, from_page_id, to_page_id, with_titles, query.limit).await
+}
 
+async fn all_shortest_path_response(
+    loaded: Arc<LoadedGraph>,
+    from_page_id: u32,
+    to_page_id: u32,
+    with_titles: bool,
+    limit: Option<usize>,
+) -> HttpResponse {
     let start_time = std::time::Instant::now();
 
-    let paths = web::block(move || {
-        find_all_shortest_path(graph, from_page_id, to_page_id)
+    let searched = Arc::clone(&loaded);
+    let shortest_paths = web::block(move || {
+        find_all_shortest_path(searched.graph(), from_page_id, to_page_id, limit)
     })
     .await
     .unwrap();
+    let graph = loaded.graph();
 
     let elapsed_time = start_time.elapsed();
 
-    let num_paths = paths.len();
-    let shortest_path_length = paths.iter().map(|path| path.len()).min().unwrap_or(0);
+    let paths = shortest_paths.paths;
+    let num_paths = shortest_paths.num_paths;
+    let num_returned_paths = paths.len();
+    let shortest_path_length = shortest_paths.path_length;
+
+    let paths: Vec<serde_json::Value> = paths.iter().map(|path| path_json(graph, path, with_titles)).collect();
 
     let response = serde_json::json!({
+        "from_page_id": from_page_id,
+        "to_page_id": to_page_id,
         "paths": paths,
         "num_paths": num_paths,
+        "num_returned_paths": num_returned_paths,
         "shortest_path_length": shortest_path_length,
         "time_spent_ms": elapsed_time.as_millis()
     });
@@ -253,23 +460,131 @@ async fn all_shortest_path(
     HttpResponse::Ok().json(response)
 }
 
+#[derive(serde::Deserialize)]
+struct SinglePathQuery {
+    with_titles: Option<bool>,
+}
+
+#[get("/shortest-path/{from_page_id}/to/{to_page_id}")]
+async fn single_shortest_path(
+    LangGraph(loaded): LangGraph,
+    path_params: web::Path<PageIds>,
+    query: web::Query<SinglePathQuery>,
+) -> HttpResponse {
+    let PageIds { from_page_id, to_page_id } = path_params.into_inner();
+    let with_titles = query.with_titles.unwrap_or(false);
+
+    let start_time = std::time::Instant::now();
+
+    let searched = Arc::clone(&loaded);
+    let path = web::block(move || {
+        find_shortest_path(searched.graph(), from_page_id, to_page_id)
+    })
+    .await
+    .unwrap();
+    let graph = loaded.graph();
+
+    let elapsed_time = start_time.elapsed();
+
+    let response = serde_json::json!({
+        "from_page_id": from_page_id,
+        "to_page_id": to_page_id,
+        "path": path_json(graph, &path, with_titles),
+        "shortest_path_length": path.len(),
+        "time_spent_ms": elapsed_time.as_millis()
+    });
+
+    HttpResponse::Ok().json(response)
+}
+
+#[get("/shortest-path-count/{from_page_id}/to/{to_page_id}")]
+async fn shortest_path_count(
+    LangGraph(loaded): LangGraph,
+    path_params: web::Path<PageIds>,
+) -> HttpResponse {
+    let PageIds { from_page_id, to_page_id } = path_params.into_inner();
+
+    let start_time = std::time::Instant::now();
+
+    let count = web::block(move || {
+        count_shortest_path(loaded.graph(), from_page_id, to_page_id)
+    })
+    .await
+    .unwrap();
+
+    let elapsed_time = start_time.elapsed();
+
+    let response = serde_json::json!({
+        "from_page_id": from_page_id,
+        "to_page_id": to_page_id,
+        "num_paths": count.num_paths,
+        "shortest_path_length": count.path_length,
+        "time_spent_ms": elapsed_time.as_millis()
+    });
+
+    HttpResponse::Ok().json(response)
+}
+
+/// Routes served both unprefixed, for the default language, and under `/{lang}`.
+fn graph_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(admin_reload)
+        .service(meta)
+        .service(all_shortest_path)
+        .service(all_shortest_path_by_title)
+        .service(single_shortest_path)
+        .service(shortest_path_count)
+        .service(search);
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
 
-    let graph = &*GRAPH;
-    let app_state = AppState { graph };
+    let graph_paths = graph_paths();
+    let default_lang = std::env::var("DEFAULT_LANG").unwrap_or_else(|_| graph_paths[0].0.clone());
+    for (lang, path) in &graph_paths {
+        let default = if *lang == default_lang { " (default)" } else { "" };
+        log::info!("Serving {}{} from {}", lang, default, path.display());
+    }
+    let app_state = AppState {
+        graphs: graph_paths.iter().map(|(lang, path)| (lang.clone(), GraphSlot::new(path.clone()))).collect(),
+        default_lang,
+        admin_token: std::env::var("ADMIN_TOKEN").ok().filter(|token| !token.is_empty()),
+    };
     let graph_data = web::Data::new(app_state);
 
-    
-    log::info!("Graph data loaded and ready.");
+    // Validating a large archive takes a while, so load every graph in the
+    // background and answer /health and /ready meanwhile.
+    for (lang, path) in graph_paths {
+        let loading_state = graph_data.clone();
+        std::thread::spawn(move || {
+            if let Err(err) = loading_state.graphs[&lang].load_graph(&lang, &path) {
+                log::error!("[{}] Failed to load graph: {}", lang, err);
+                std::process::exit(1);
+            }
+        });
+    }
 
-    log::info!("Graph edges {}", graph.edges.len());
-    log::info!("Graph offsets {}", graph.offsets.len());
-    log::info!("Graph  pages offsets {}", graph.offsets[67819+1]);
-    log::info!("Graph  pages offsets {}", graph.offsets[67819+2]);
-    for v  in graph.offsets.to_vec().iter().rev().take(10) {
-        log::info!("- {}", v);
+    // SIGHUP reloads the graph files currently served, e.g. after replacing them.
+    #[cfg(unix)]
+    {
+        let reload_state = graph_data.clone();
+        actix_web::rt::spawn(async move {
+            let mut hangup = signal(SignalKind::hangup()).expect("Failed to listen for SIGHUP");
+            while hangup.recv().await.is_some() {
+                log::info!("SIGHUP received, reloading graphs");
+                let loading_state = reload_state.clone();
+                web::block(move || {
+                    for (lang, slot) in &loading_state.graphs {
+                        if let Err(err) = slot.load_graph(lang, &slot.current_graph_path()) {
+                            log::error!("[{}] Failed to reload graph, still serving the previous one: {}", lang, err);
+                        }
+                    }
+                })
+                .await
+                .unwrap();
+            }
+        });
     }
 
     let port_str = std::env::var("PORT").unwrap_or_else(|_| "8080".to_string());
@@ -280,9 +595,103 @@ async fn main() -> std::io::Result<()> {
         App::new()
             .wrap(Cors::default().allow_any_origin()) // Add CORS middleware to allow all origins
             .app_data(graph_data.clone())
-            .service(all_shortest_path)
+            .service(health)
+            .service(ready)
+            .configure(graph_routes)
+            // Registered last so unprefixed routes are matched first.
+            .service(web::scope("/{lang}").configure(graph_routes))
     })
     .bind(("0.0.0.0", port))?
     .run()
     .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test;
+    use rust_serverless::adjacency::Adjacency;
+    use rust_serverless::graph::{CsrGraph, GraphMeta, GRAPH_FORMAT_VERSION};
+    use rust_serverless::migrate::graph_file_bytes;
+    use std::collections::HashMap;
+
+    // Layers of two nodes between the first and the last page, each node
+    // linking to both nodes of the next layer.
+    const LAYERS: u32 = 64;
+    const LAST_PAGE_ID: u32 = 2 * LAYERS + 2;
+
+    /// Serves a graph with 2^LAYERS shortest paths from page 1 to `LAST_PAGE_ID`,
+    /// more than a `u64` holds.
+    fn layered_graph_state(name: &str) -> web::Data<AppState> {
+        let node_count = 2 * LAYERS + 2;
+        let last = node_count - 1;
+        // Node 0 links to the first layer, nodes 2i + 1 and 2i + 2 form layer i.
+        let mut links = vec![(0, 1), (0, 2)];
+        for layer in 0..LAYERS {
+            let next: &[u32] = if layer + 1 < LAYERS { &[2 * layer + 3, 2 * layer + 4] } else { &[last] };
+            for from in [2 * layer + 1, 2 * layer + 2] {
+                links.extend(next.iter().map(|&to| (from, to)));
+            }
+        }
+        let adjacency = |links: &[(u32, u32)]| {
+            let mut offsets = vec![0];
+            let mut edges = Vec::new();
+            for node in 0..node_count {
+                edges.extend(links.iter().filter(|&&(from, _)| from == node).map(|&(_, to)| to));
+                offsets.push(edges.len() as u32);
+            }
+            Adjacency::Raw { offsets, edges }
+        };
+        let reverse: Vec<(u32, u32)> = links.iter().map(|&(from, to)| (to, from)).collect();
+        let graph = CsrGraph {
+            meta: GraphMeta {
+                format_version: GRAPH_FORMAT_VERSION,
+                lang: "en".to_string(),
+                dumps: Vec::new(),
+                builder_version: String::new(),
+                built_at: 0,
+                build_duration_ms: 0,
+                node_count,
+                edge_count: links.len() as u64,
+            },
+            links: adjacency(&links),
+            reverse_links: adjacency(&reverse),
+            page_ids: (1..=node_count).collect(),
+            sorted_nodes: Vec::new(),
+            pages: HashMap::new(),
+            redirects: HashMap::new(),
+            titles: vec![String::new(); node_count as usize],
+            title_index: Vec::new(),
+        };
+
+        let path = std::env::temp_dir().join(format!("rust-serverless-{}-{}.rkyv", std::process::id(), name));
+        std::fs::write(&path, graph_file_bytes(&graph).unwrap()).unwrap();
+        let slot = GraphSlot::new(path.clone());
+        slot.load_graph("en", &path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        web::Data::new(AppState {
+            graphs: BTreeMap::from([("en".to_string(), slot)]),
+            default_lang: "en".to_string(),
+            admin_token: None,
+        })
+    }
+
+    #[actix_web::test]
+    async fn all_shortest_paths_report_counts_above_u64() {
+        let app = test::init_service(App::new().app_data(layered_graph_state("all")).configure(graph_routes)).await;
+        let request = test::TestRequest::get().uri(&format!("/all-shortest-path/1/to/{}?limit=1", LAST_PAGE_ID)).to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(body["num_paths"].to_string(), (1u128 << LAYERS).to_string());
+        assert_eq!(body["num_returned_paths"], 1);
+        assert_eq!(body["shortest_path_length"], LAYERS + 2);
+    }
+
+    #[actix_web::test]
+    async fn shortest_path_count_reports_counts_above_u64() {
+        let app = test::init_service(App::new().app_data(layered_graph_state("count")).configure(graph_routes)).await;
+        let request = test::TestRequest::get().uri(&format!("/shortest-path-count/1/to/{}", LAST_PAGE_ID)).to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(body["num_paths"].to_string(), (1u128 << LAYERS).to_string());
+        assert_eq!(body["shortest_path_length"], LAYERS + 2);
+    }
+}
@@ -0,0 +1,183 @@
+use rkyv::{access, rancor, to_bytes, Archive, Deserialize};
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::adjacency::Adjacency;
+use crate::graph::{ArchivedCsrGraph, CsrGraph, GraphMeta, WikiPageId, GRAPH_FORMAT_VERSION, GRAPH_HEADER_LEN, GRAPH_MAGIC};
+
+/// Layout of format version 1, which mapped page ids and node indices with
+/// two hash maps instead of the sorted `page_ids`.
+#[derive(Archive, Deserialize)]
+struct CsrGraphV1 {
+    meta: GraphMeta,
+    offsets: Vec<u32>,
+    edges: Vec<u32>,
+    reverse_offsets: Vec<u32>,
+    reverse_edges: Vec<u32>,
+    page_id_to_index: HashMap<u32, u32>,
+    index_to_page_id: HashMap<u32, u32>,
+    pages: HashMap<String, WikiPageId>,
+    redirects: HashMap<u32, String>,
+    titles: Vec<String>,
+    title_index: Vec<u32>,
+}
+
+/// Layout of format version 2, with raw offsets and edges arrays instead of
+/// the `Adjacency` of each direction.
+#[derive(Archive, Deserialize)]
+struct CsrGraphV2 {
+    meta: GraphMeta,
+    offsets: Vec<u32>,
+    edges: Vec<u32>,
+    reverse_offsets: Vec<u32>,
+    reverse_edges: Vec<u32>,
+    page_ids: Vec<u32>,
+    pages: HashMap<String, WikiPageId>,
+    redirects: HashMap<u32, String>,
+    titles: Vec<String>,
+    title_index: Vec<u32>,
+}
+
+/// Layout of format version 3, whose nodes were always indexed in page id order.
+#[derive(Archive, Deserialize)]
+struct CsrGraphV3 {
+    meta: GraphMeta,
+    links: Adjacency,
+    reverse_links: Adjacency,
+    page_ids: Vec<u32>,
+    pages: HashMap<String, WikiPageId>,
+    redirects: HashMap<u32, String>,
+    titles: Vec<String>,
+    title_index: Vec<u32>,
+}
+
+#[derive(Debug)]
+pub enum MigrateError {
+    /// The bytes do not start with the graph header.
+    UnknownFormat,
+    /// No migration exists from this format version.
+    UnsupportedVersion(u32),
+    Archive(rancor::Error),
+}
+
+impl fmt::Display for MigrateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrateError::UnknownFormat => write!(f, "no graph header, the graph predates format versioning and must be rebuilt"),
+            MigrateError::UnsupportedVersion(version) => write!(
+                f,
+                "cannot migrate format version {} to version {}",
+                version,
+                GRAPH_FORMAT_VERSION
+            ),
+            MigrateError::Archive(source) => write!(f, "invalid archived graph: {}", source),
+        }
+    }
+}
+
+impl std::error::Error for MigrateError {}
+
+/// Format version of a graph file, `None` when it has no graph header.
+pub fn format_version(bytes: &[u8]) -> Option<u32> {
+    if bytes.len() < GRAPH_HEADER_LEN || bytes[..GRAPH_MAGIC.len()] != GRAPH_MAGIC {
+        return None;
+    }
+    Some(u32::from_le_bytes(bytes[GRAPH_MAGIC.len()..GRAPH_MAGIC.len() + 4].try_into().unwrap()))
+}
+
+/// The header followed by the archive, as written to `graph.rkyv`.
+pub fn graph_file_bytes(graph: &CsrGraph) -> Result<Vec<u8>, rancor::Error> {
+    let archive = to_bytes::<rancor::Error>(graph)?;
+    let mut bytes = Vec::with_capacity(GRAPH_HEADER_LEN + archive.len());
+    bytes.extend_from_slice(&GRAPH_MAGIC);
+    bytes.extend_from_slice(&GRAPH_FORMAT_VERSION.to_le_bytes());
+    bytes.resize(GRAPH_HEADER_LEN, 0);
+    bytes.extend_from_slice(&archive);
+    Ok(bytes)
+}
+
+/// Convert the content of an older graph file to the current `CsrGraph`,
+/// upgrading it one format version at a time.
+pub fn migrate(bytes: &[u8]) -> Result<CsrGraph, MigrateError> {
+    let archive = &bytes[GRAPH_HEADER_LEN.min(bytes.len())..];
+    match format_version(bytes) {
+        None => Err(MigrateError::UnknownFormat),
+        Some(1) => Ok(v3_to_v4(v2_to_v3(v1_to_v2(deserialize_v1(archive)?)))),
+        Some(2) => Ok(v3_to_v4(v2_to_v3(deserialize_v2(archive)?))),
+        Some(3) => Ok(v3_to_v4(deserialize_v3(archive)?)),
+        Some(version) => Err(MigrateError::UnsupportedVersion(version)),
+    }
+}
+
+fn deserialize_v1(archive: &[u8]) -> Result<CsrGraphV1, MigrateError> {
+    let archived = access::<ArchivedCsrGraphV1, rancor::Error>(archive).map_err(MigrateError::Archive)?;
+    rkyv::deserialize::<CsrGraphV1, rancor::Error>(archived).map_err(MigrateError::Archive)
+}
+
+fn deserialize_v2(archive: &[u8]) -> Result<CsrGraphV2, MigrateError> {
+    let archived = access::<ArchivedCsrGraphV2, rancor::Error>(archive).map_err(MigrateError::Archive)?;
+    rkyv::deserialize::<CsrGraphV2, rancor::Error>(archived).map_err(MigrateError::Archive)
+}
+
+fn deserialize_v3(archive: &[u8]) -> Result<CsrGraphV3, MigrateError> {
+    let archived = access::<ArchivedCsrGraphV3, rancor::Error>(archive).map_err(MigrateError::Archive)?;
+    rkyv::deserialize::<CsrGraphV3, rancor::Error>(archived).map_err(MigrateError::Archive)
+}
+
+fn v1_to_v2(graph: CsrGraphV1) -> CsrGraphV2 {
+    // Indices were assigned in page id order, so reading the ids back by
+    // index yields the sorted page ids.
+    let page_ids: Vec<u32> = (0..graph.index_to_page_id.len() as u32)
+        .map(|index| graph.index_to_page_id[&index])
+        .collect();
+
+    CsrGraphV2 {
+        meta: graph.meta,
+        offsets: graph.offsets,
+        edges: graph.edges,
+        reverse_offsets: graph.reverse_offsets,
+        reverse_edges: graph.reverse_edges,
+        page_ids,
+        pages: graph.pages,
+        redirects: graph.redirects,
+        titles: graph.titles,
+        title_index: graph.title_index,
+    }
+}
+
+fn v2_to_v3(graph: CsrGraphV2) -> CsrGraphV3 {
+    CsrGraphV3 {
+        meta: graph.meta,
+        links: Adjacency::Raw { offsets: graph.offsets, edges: graph.edges },
+        reverse_links: Adjacency::Raw { offsets: graph.reverse_offsets, edges: graph.reverse_edges },
+        page_ids: graph.page_ids,
+        pages: graph.pages,
+        redirects: graph.redirects,
+        titles: graph.titles,
+        title_index: graph.title_index,
+    }
+}
+
+fn v3_to_v4(graph: CsrGraphV3) -> CsrGraph {
+    CsrGraph {
+        meta: GraphMeta { format_version: GRAPH_FORMAT_VERSION, ..graph.meta },
+        links: graph.links,
+        reverse_links: graph.reverse_links,
+        page_ids: graph.page_ids,
+        // Graphs before version 4 were never reordered.
+        sorted_nodes: Vec::new(),
+        pages: graph.pages,
+        redirects: graph.redirects,
+        titles: graph.titles,
+        title_index: graph.title_index,
+    }
+}
+
+/// Read a graph file of any supported format version.
+pub fn read_graph(bytes: &[u8]) -> Result<CsrGraph, MigrateError> {
+    if format_version(bytes) != Some(GRAPH_FORMAT_VERSION) {
+        return migrate(bytes);
+    }
+    let archived = access::<ArchivedCsrGraph, rancor::Error>(&bytes[GRAPH_HEADER_LEN..]).map_err(MigrateError::Archive)?;
+    rkyv::deserialize::<CsrGraph, rancor::Error>(archived).map_err(MigrateError::Archive)
+}
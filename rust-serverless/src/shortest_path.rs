@@ -0,0 +1,894 @@
+use rayon::prelude::*;
+use rustc_hash::{FxBuildHasher, FxHashMap};
+
+use crate::adjacency::ArchivedAdjacency;
+use crate::graph::{page_id, page_index, ArchivedCsrGraph};
+use crate::workspace::{with_workspace, SearchWorkspace, StampedArray};
+
+/// What the bidirectional BFS remembers about how each visited node was reached.
+pub trait SearchRecord {
+    /// `node` is the root of this direction of the search.
+    fn root(&mut self, node: u32);
+    /// `node` is reached for the first time, from `parent`.
+    fn discover(&mut self, node: u32, parent: u32);
+    /// `node` is reached again from another `parent`, at the same depth.
+    fn rediscover(&mut self, node: u32, parent: u32);
+    /// The search is over, no more nodes will be reached.
+    fn finish(&mut self) {}
+}
+
+/// Every parent of each visited node, forming a DAG from which all shortest
+/// paths can be rebuilt.
+///
+/// Links are appended as `(node, parent)` pairs and sorted once the search is
+/// done, rather than keeping a separately allocated list per visited node.
+#[derive(Default)]
+pub struct ParentLists(Vec<(u32, u32)>);
+
+impl ParentLists {
+    /// Parents of `node`, only valid once the search is finished.
+    fn parents(&self, node: u32) -> &[(u32, u32)] {
+        let start = self.0.partition_point(|&(child, _)| child < node);
+        let len = self.0[start..].partition_point(|&(child, _)| child == node);
+        &self.0[start..start + len]
+    }
+}
+
+impl SearchRecord for ParentLists {
+    fn root(&mut self, _node: u32) {}
+
+    fn discover(&mut self, node: u32, parent: u32) {
+        self.0.push((node, parent));
+    }
+
+    fn rediscover(&mut self, node: u32, parent: u32) {
+        self.0.push((node, parent));
+    }
+
+    fn finish(&mut self) {
+        self.0.sort_unstable();
+    }
+}
+
+/// Number of shortest paths from the root to each visited node, the sum of
+/// the counts of its parents, kept in a workspace array.
+pub struct PathCounts<'a>(&'a mut StampedArray<u128>);
+
+impl PathCounts<'_> {
+    fn count(&self, node: u32) -> u128 {
+        self.0.get(node).expect("Path counts are only read for visited nodes")
+    }
+}
+
+impl SearchRecord for PathCounts<'_> {
+    fn root(&mut self, node: u32) {
+        self.0.set(node, 1);
+    }
+
+    fn discover(&mut self, node: u32, parent: u32) {
+        let count = self.count(parent);
+        self.0.set(node, count);
+    }
+
+    fn rediscover(&mut self, node: u32, parent: u32) {
+        let count = self.count(node).saturating_add(self.count(parent));
+        self.0.set(node, count);
+    }
+}
+
+/// Result of the bidirectional BFS: what each direction recorded and the
+/// nodes where they meet.
+pub struct BidirectionalSearch<R> {
+    start_node: u32,
+    end_node: u32,
+    forward: R,
+    backward: R,
+    meeting_nodes: Vec<u32>,
+    /// Number of edges of every shortest path.
+    length: u32,
+}
+
+pub type ShortestPathSearch = BidirectionalSearch<ParentLists>;
+
+/// Shortest paths between two pages, as page ids.
+pub struct ShortestPaths {
+    pub paths: Vec<Vec<u32>>,
+    /// Exact number of shortest paths, saturating at `u128::MAX`.
+    pub num_paths: u128,
+    /// Number of pages in each path, 0 when there is no path.
+    pub path_length: usize,
+}
+
+/// Number of shortest paths between two pages.
+pub struct ShortestPathCount {
+    /// Exact number of shortest paths, saturating at `u128::MAX`.
+    pub num_paths: u128,
+    /// Number of pages in each path, 0 when there is no path.
+    pub path_length: usize,
+}
+
+pub fn find_all_shortest_path(
+    graph: &ArchivedCsrGraph,
+    start_page_id: u32,
+    end_page_id: u32,
+    limit: Option<usize>,
+) -> ShortestPaths {
+    let no_path = ShortestPaths { paths: vec![], num_paths: 0, path_length: 0 };
+    let (Some(start_node), Some(end_node)) = (page_index(graph, start_page_id), page_index(graph, end_page_id)) else {
+        return no_path;
+    };
+
+    let search: ShortestPathSearch = match bidirectional_bfs(graph, start_node, end_node) {
+        Some(search) => search,
+        None => return no_path,
+    };
+
+    let paths = search
+        .paths()
+        .take(limit.unwrap_or(usize::MAX))
+        .map(|path| {
+            path.into_iter().map(|idx| page_id(graph, idx)).collect()
+        })
+        .collect();
+
+    ShortestPaths {
+        paths,
+        num_paths: search.count_paths(),
+        path_length: search.length as usize + 1,
+    }
+}
+
+/// Count the shortest paths between two pages without rebuilding them, by
+/// multiplying the path counts of both directions at each meeting node.
+pub fn count_shortest_path(
+    graph: &ArchivedCsrGraph,
+    start_page_id: u32,
+    end_page_id: u32,
+) -> ShortestPathCount {
+    let no_path = ShortestPathCount { num_paths: 0, path_length: 0 };
+    let (Some(start_node), Some(end_node)) = (page_index(graph, start_page_id), page_index(graph, end_page_id)) else {
+        return no_path;
+    };
+    count_paths_between(graph, start_node, end_node, Expansion::Auto)
+}
+
+fn count_paths_between(graph: &ArchivedCsrGraph, start_node: u32, end_node: u32, expansion: Expansion) -> ShortestPathCount {
+    let no_path = ShortestPathCount { num_paths: 0, path_length: 0 };
+    if start_node == end_node {
+        return ShortestPathCount { num_paths: 1, path_length: 1 };
+    }
+
+    let node_count = graph.links.node_count() as usize;
+    with_workspace(node_count, |workspace| {
+        let SearchWorkspace { forward, backward, forward_counts, backward_counts } = workspace;
+        forward_counts.reset(node_count);
+        backward_counts.reset(node_count);
+        let records = (PathCounts(forward_counts), PathCounts(backward_counts));
+        let Some(search) = bidirectional_bfs_in_workspace(graph, start_node, end_node, records, forward, backward, expansion) else {
+            return no_path;
+        };
+
+        let num_paths = search
+            .meeting_nodes
+            .iter()
+            .map(|&meet_node| search.forward.count(meet_node).saturating_mul(search.backward.count(meet_node)))
+            .fold(0, u128::saturating_add);
+
+        ShortestPathCount {
+            num_paths,
+            path_length: search.length as usize + 1,
+        }
+    })
+}
+
+pub fn bidirectional_bfs<R: SearchRecord + Default>(
+    graph: &ArchivedCsrGraph,
+    start_node: u32,
+    end_node: u32,
+) -> Option<BidirectionalSearch<R>> {
+    bidirectional_bfs_with(graph, start_node, end_node, Expansion::Auto)
+}
+
+fn bidirectional_bfs_with<R: SearchRecord + Default>(
+    graph: &ArchivedCsrGraph,
+    start_node: u32,
+    end_node: u32,
+    expansion: Expansion,
+) -> Option<BidirectionalSearch<R>> {
+    let mut forward = R::default();
+    let mut backward = R::default();
+
+    if start_node == end_node {
+        forward.root(start_node);
+        backward.root(end_node);
+        return Some(BidirectionalSearch {
+            start_node,
+            end_node,
+            forward,
+            backward,
+            meeting_nodes: vec![start_node],
+            length: 0,
+        });
+    }
+
+    with_workspace(graph.links.node_count() as usize, |workspace| {
+        bidirectional_bfs_in_workspace(graph, start_node, end_node, (forward, backward), &mut workspace.forward, &mut workspace.backward, expansion)
+    })
+}
+
+/// Bidirectional BFS between two distinct nodes, with the visited arrays of a
+/// workspace holding the distance of every node from its direction's root.
+fn bidirectional_bfs_in_workspace<R: SearchRecord>(
+    graph: &ArchivedCsrGraph,
+    start_node: u32,
+    end_node: u32,
+    (mut forward, mut backward): (R, R),
+    forward_dist: &mut StampedArray,
+    backward_dist: &mut StampedArray,
+    expansion: Expansion,
+) -> Option<BidirectionalSearch<R>> {
+    forward.root(start_node);
+    backward.root(end_node);
+
+    // Each node is pushed once when first reached, so plain vectors hold a level.
+    let mut forward_frontier = vec![start_node];
+    let mut backward_frontier = vec![end_node];
+
+    // How nodes were reached is kept by the records.
+    forward_dist.set(start_node, 0);
+    backward_dist.set(end_node, 0);
+
+    let outgoing = &graph.links;
+    let incoming = &graph.reverse_links;
+
+    let mut meeting_nodes = Vec::new();
+    let mut shortest_path_len = u32::MAX;
+    let mut forward_depth = 0;
+    let mut backward_depth = 0;
+    // Links leaving the nodes already expanded by each direction.
+    let mut forward_explored_links = 0;
+    let mut backward_explored_links = 0;
+
+    while !forward_frontier.is_empty() && !backward_frontier.is_empty() {
+        // Stop if we can't find a shorter path than we've already found.
+        if forward_depth + backward_depth >= shortest_path_len {
+            break;
+        }
+
+        // Python script trick: expand the frontier with fewer outgoing links.
+        let forward_link_count: usize = forward_frontier.par_iter().map(|&u| outgoing.degree(u)).sum();
+        let backward_link_count: usize = backward_frontier.par_iter().map(|&u| incoming.degree(u)).sum();
+
+        let expand_forward = forward_link_count <= backward_link_count;
+
+        if expand_forward {
+            forward_depth += 1;
+            let bottom_up = expansion.bottom_up(forward_link_count, outgoing.edge_count().saturating_sub(forward_explored_links));
+            forward_explored_links += forward_link_count;
+            forward_frontier = expand_level(
+                outgoing,
+                incoming,
+                &forward_frontier,
+                forward_dist,
+                forward_depth,
+                &mut forward,
+                bottom_up,
+            );
+
+            // Check for intersections with the backward search's visited nodes.
+            for &node in &forward_frontier {
+                if let Some(bwd_dist) = backward_dist.get(node) {
+                    let path_len = forward_depth + bwd_dist;
+                    if path_len < shortest_path_len {
+                        shortest_path_len = path_len;
+                        meeting_nodes.clear();
+                        meeting_nodes.push(node);
+                    } else if path_len == shortest_path_len {
+                        meeting_nodes.push(node);
+                    }
+                }
+            }
+        } else { // Expand backward
+            backward_depth += 1;
+            let bottom_up = expansion.bottom_up(backward_link_count, incoming.edge_count().saturating_sub(backward_explored_links));
+            backward_explored_links += backward_link_count;
+            backward_frontier = expand_level(
+                incoming,
+                outgoing,
+                &backward_frontier,
+                backward_dist,
+                backward_depth,
+                &mut backward,
+                bottom_up,
+            );
+
+            // Check for intersections with the forward search's visited nodes.
+            for &node in &backward_frontier {
+                if let Some(fwd_dist) = forward_dist.get(node) {
+                    let path_len = backward_depth + fwd_dist;
+                    if path_len < shortest_path_len {
+                        shortest_path_len = path_len;
+                        meeting_nodes.clear();
+                        meeting_nodes.push(node);
+                    } else if path_len == shortest_path_len {
+                        meeting_nodes.push(node);
+                    }
+                }
+            }
+        }
+    }
+
+    if meeting_nodes.is_empty() {
+        return None;
+    }
+
+    // Every meeting node sits at the same depth, so each shortest path goes
+    // through exactly one of them and no deduplication is needed.
+    meeting_nodes.sort_unstable();
+
+    forward.finish();
+    backward.finish();
+
+    Some(BidirectionalSearch {
+        start_node,
+        end_node,
+        forward,
+        backward,
+        meeting_nodes,
+        length: shortest_path_len,
+    })
+}
+
+// Frontiers smaller than this are expanded on the current thread.
+const PARALLEL_MIN_FRONTIER: usize = 1024;
+// Go bottom-up once the frontier holds more than 1/ALPHA of the links left to
+// explore, the switch point suggested by Beamer et al.
+const BOTTOM_UP_ALPHA: usize = 14;
+
+fn prefer_bottom_up(frontier_link_count: usize, unexplored_link_count: usize) -> bool {
+    frontier_link_count > unexplored_link_count / BOTTOM_UP_ALPHA
+}
+
+/// How each level of a search is expanded, only forced by the tests.
+#[derive(Clone, Copy, Debug)]
+enum Expansion {
+    Auto,
+    #[cfg_attr(not(test), allow(dead_code))]
+    TopDown,
+    #[cfg_attr(not(test), allow(dead_code))]
+    BottomUp,
+}
+
+impl Expansion {
+    fn bottom_up(self, frontier_link_count: usize, unexplored_link_count: usize) -> bool {
+        match self {
+            Expansion::Auto => prefer_bottom_up(frontier_link_count, unexplored_link_count),
+            Expansion::TopDown => false,
+            Expansion::BottomUp => true,
+        }
+    }
+}
+
+/// Expand one level of a direction of the BFS and return the next frontier.
+///
+/// The `(node, parent)` links reaching nodes unvisited at the start of the
+/// level are gathered in parallel against the read-only visited array, then
+/// merged on the current thread. A node is new for its first link
+/// and every later one is another parent at the same depth, so the recorded
+/// shortest path DAG is the same whichever way the level is expanded. Links
+/// duplicated in the graph are only recorded once, so path counts match the
+/// paths enumerated from the DAG.
+///
+/// Top-down walks the links leaving the frontier. Bottom-up instead scans
+/// every unvisited node for links coming from the frontier, which is cheaper
+/// once the frontier reaches most of the graph.
+fn expand_level<R: SearchRecord>(
+    links: &ArchivedAdjacency,
+    reverse_links: &ArchivedAdjacency,
+    frontier: &[u32],
+    dist: &mut StampedArray,
+    depth: u32,
+    record: &mut R,
+    bottom_up: bool,
+) -> Vec<u32> {
+    let visited: &StampedArray = dist;
+    let mut candidates: Vec<(u32, u32)> = if bottom_up {
+        (0..reverse_links.node_count())
+            .into_par_iter()
+            .with_min_len(PARALLEL_MIN_FRONTIER)
+            .filter(|&v| !visited.contains(v))
+            .flat_map_iter(|v| {
+                reverse_links
+                    .neighbours(v)
+                    .filter(|&u| visited.get(u) == Some(depth - 1))
+                    .map(move |u| (v, u))
+            })
+            .collect()
+    } else {
+        let top_down = |&u: &u32| {
+            links
+                .neighbours(u)
+                .filter(|&v| !visited.contains(v))
+                .map(move |v| (v, u))
+        };
+        if frontier.len() < PARALLEL_MIN_FRONTIER {
+            frontier.iter().flat_map(top_down).collect()
+        } else {
+            frontier.par_iter().with_min_len(PARALLEL_MIN_FRONTIER / 8).flat_map_iter(top_down).collect()
+        }
+    };
+    // Candidates come grouped by parent top-down and by node bottom-up, so a
+    // duplicated link is made adjacent by sorting the small group it is in.
+    let same_group = |a: &(u32, u32), b: &(u32, u32)| if bottom_up { a.0 == b.0 } else { a.1 == b.1 };
+    if candidates.len() < PARALLEL_MIN_FRONTIER {
+        candidates.chunk_by_mut(same_group).for_each(|group| group.sort_unstable());
+    } else {
+        candidates.par_chunk_by_mut(same_group).for_each(|group| group.sort_unstable());
+    }
+    candidates.dedup();
+
+    let mut next_frontier = Vec::new();
+    for (v, u) in candidates {
+        if dist.contains(v) {
+            record.rediscover(v, u);
+        } else {
+            dist.set(v, depth);
+            record.discover(v, u);
+            next_frontier.push(v);
+        }
+    }
+    next_frontier
+}
+
+impl ShortestPathSearch {
+    /// Lazily enumerate every shortest path as node indices, one meeting node
+    /// at a time, without materialising the Cartesian product.
+    pub fn paths(&self) -> impl Iterator<Item = Vec<u32>> + '_ {
+        self.meeting_nodes.iter().flat_map(move |&meet_node| {
+            ParentPaths::new(&self.forward, meet_node, self.start_node).flat_map(move |mut forward_path| {
+                forward_path.reverse();
+                ParentPaths::new(&self.backward, meet_node, self.end_node).map(move |backward_path| {
+                    let mut path = forward_path.clone();
+                    path.extend_from_slice(&backward_path[1..]);
+                    path
+                })
+            })
+        })
+    }
+
+    /// Exact number of shortest paths, computed by dynamic programming over
+    /// the parent DAG of each direction instead of enumerating them.
+    pub fn count_paths(&self) -> u128 {
+        let mut forward_counts = FxHashMap::with_hasher(FxBuildHasher);
+        let mut backward_counts = FxHashMap::with_hasher(FxBuildHasher);
+        self.meeting_nodes
+            .iter()
+            .map(|&meet_node| {
+                let forward = count_parent_paths(meet_node, self.start_node, &self.forward, &mut forward_counts);
+                let backward = count_parent_paths(meet_node, self.end_node, &self.backward, &mut backward_counts);
+                forward.saturating_mul(backward)
+            })
+            .fold(0, u128::saturating_add)
+    }
+}
+
+/// Number of paths from `root` to `node` in a parent DAG, memoised in `counts`.
+fn count_parent_paths(
+    node: u32,
+    root: u32,
+    parents: &ParentLists,
+    counts: &mut FxHashMap<u32, u128>,
+) -> u128 {
+    if node == root {
+        return 1;
+    }
+    if let Some(&count) = counts.get(&node) {
+        return count;
+    }
+    let count = parents
+        .parents(node)
+        .iter()
+        .map(|&(_, parent_node)| count_parent_paths(parent_node, root, parents, counts))
+        .fold(0, u128::saturating_add);
+    counts.insert(node, count);
+    count
+}
+
+/// Depth first walk of a parent DAG yielding every path from `node` up to
+/// `root`, keeping only the current path in memory.
+struct ParentPaths<'a> {
+    parents: &'a ParentLists,
+    root: u32,
+    // Each entry is a node of the current path and which of its parents comes next.
+    stack: Vec<(u32, usize)>,
+    started: bool,
+}
+
+impl<'a> ParentPaths<'a> {
+    fn new(parents: &'a ParentLists, node: u32, root: u32) -> Self {
+        Self { parents, root, stack: vec![(node, 0)], started: false }
+    }
+
+    /// Follow the currently selected parents from the top of the stack to the root.
+    fn descend(&mut self) -> Option<Vec<u32>> {
+        while let Some(&(node, choice)) = self.stack.last() {
+            if node == self.root {
+                return Some(self.stack.iter().map(|&(node, _)| node).collect());
+            }
+            let (_, parent_node) = *self.parents.parents(node).get(choice)?;
+            self.stack.push((parent_node, 0));
+        }
+        None
+    }
+}
+
+impl Iterator for ParentPaths<'_> {
+    type Item = Vec<u32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            return self.descend();
+        }
+        // Drop the root then move the deepest node that has another parent to it.
+        self.stack.pop();
+        while let Some((node, choice)) = self.stack.last_mut() {
+            *choice += 1;
+            let has_next_parent = *choice < self.parents.parents(*node).len();
+            if has_next_parent {
+                return self.descend();
+            }
+            self.stack.pop();
+        }
+        None
+    }
+}
+
+/// Find a single shortest path between two pages, as page ids.
+///
+/// Each direction keeps one parent per node in a flat array indexed by
+/// node, and the search stops at the first node reached by both sides:
+/// as no node was shared before this level, that path is already minimal.
+pub fn find_shortest_path(
+    graph: &ArchivedCsrGraph,
+    start_page_id: u32,
+    end_page_id: u32,
+) -> Vec<u32> {
+    let (Some(start_node), Some(end_node)) = (page_index(graph, start_page_id), page_index(graph, end_page_id)) else {
+        return vec![];
+    };
+    if start_node == end_node {
+        return vec![start_page_id];
+    }
+
+    let path = with_workspace(graph.links.node_count() as usize, |workspace| {
+        let SearchWorkspace { forward: forward_parent, backward: backward_parent, .. } = workspace;
+        forward_parent.set(start_node, start_node);
+        backward_parent.set(end_node, end_node);
+
+        let mut forward_frontier = vec![start_node];
+        let mut backward_frontier = vec![end_node];
+        let mut meeting_node = None;
+
+        while meeting_node.is_none() && !forward_frontier.is_empty() && !backward_frontier.is_empty() {
+            let forward_link_count: usize = forward_frontier.iter().map(|&u| graph.links.degree(u)).sum();
+            let backward_link_count: usize = backward_frontier.iter().map(|&u| graph.reverse_links.degree(u)).sum();
+
+            meeting_node = if forward_link_count <= backward_link_count {
+                expand_single_parent(&graph.links, &mut forward_frontier, forward_parent, backward_parent)
+            } else {
+                expand_single_parent(&graph.reverse_links, &mut backward_frontier, backward_parent, forward_parent)
+            };
+        }
+
+        let meeting_node = meeting_node?;
+
+        let mut path = vec![meeting_node];
+        let mut node = meeting_node;
+        while node != start_node {
+            node = forward_parent.get(node).unwrap();
+            path.push(node);
+        }
+        path.reverse();
+        let mut node = meeting_node;
+        while node != end_node {
+            node = backward_parent.get(node).unwrap();
+            path.push(node);
+        }
+        Some(path)
+    });
+
+    path.unwrap_or_default()
+        .into_iter()
+        .map(|idx| page_id(graph, idx))
+        .collect()
+}
+
+/// Expand one level of a direction of `find_shortest_path`, returning the
+/// first node already reached by the other direction.
+fn expand_single_parent(
+    links: &ArchivedAdjacency,
+    frontier: &mut Vec<u32>,
+    parent: &mut StampedArray,
+    other_parent: &StampedArray,
+) -> Option<u32> {
+    let mut next_frontier = Vec::with_capacity(frontier.len() * 5);
+    for &u in frontier.iter() {
+        for v in links.neighbours(u) {
+            if parent.contains(v) {
+                continue;
+            }
+            parent.set(v, u);
+            if other_parent.contains(v) {
+                return Some(v);
+            }
+            next_frontier.push(v);
+        }
+    }
+    *frontier = next_frontier;
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adjacency::Adjacency;
+    use crate::graph::{CsrGraph, GraphMeta, GRAPH_FORMAT_VERSION};
+    use rkyv::rancor;
+    use rkyv::util::AlignedVec;
+    use std::collections::HashMap;
+
+    /// Page id of node `index` in the test graphs, distinct from the index.
+    fn test_page_id(index: u32) -> u32 {
+        index * 10 + 7
+    }
+
+    /// Archive of a graph of `node_count` nodes with `links` kept in the
+    /// given order, duplicates included.
+    fn graph_bytes(node_count: u32, links: &[(u32, u32)]) -> AlignedVec {
+        let adjacency = |links: &mut dyn Iterator<Item = (u32, u32)>| {
+            let mut lists = vec![Vec::new(); node_count as usize];
+            for (from, to) in links {
+                lists[from as usize].push(to);
+            }
+            let mut offsets = vec![0];
+            let mut edges = Vec::new();
+            for list in lists {
+                edges.extend(list);
+                offsets.push(edges.len() as u32);
+            }
+            Adjacency::Raw { offsets, edges }
+        };
+        let graph = CsrGraph {
+            meta: GraphMeta {
+                format_version: GRAPH_FORMAT_VERSION,
+                lang: "test".to_string(),
+                dumps: Vec::new(),
+                builder_version: String::new(),
+                built_at: 0,
+                build_duration_ms: 0,
+                node_count,
+                edge_count: links.len() as u64,
+            },
+            links: adjacency(&mut links.iter().copied()),
+            reverse_links: adjacency(&mut links.iter().map(|&(from, to)| (to, from))),
+            page_ids: (0..node_count).map(test_page_id).collect(),
+            sorted_nodes: Vec::new(),
+            pages: HashMap::new(),
+            redirects: HashMap::new(),
+            titles: vec![String::new(); node_count as usize],
+            title_index: Vec::new(),
+        };
+        rkyv::to_bytes::<rancor::Error>(&graph).unwrap()
+    }
+
+    fn archived(bytes: &AlignedVec) -> &ArchivedCsrGraph {
+        rkyv::access::<ArchivedCsrGraph, rancor::Error>(bytes).unwrap()
+    }
+
+    /// Parent DAG of three layers between node 6 and root 0: 0 -> {1, 2, 3} -> {4, 5} -> 6.
+    fn layered_parents() -> ParentLists {
+        let mut links = Vec::new();
+        for middle in [1, 2, 3] {
+            links.push((middle, 0));
+            for upper in [4, 5] {
+                links.push((upper, middle));
+            }
+        }
+        links.extend([(6, 5), (6, 4)]);
+        let mut parents = ParentLists(links);
+        parents.finish();
+        parents
+    }
+
+    #[test]
+    fn parent_paths_yield_every_path_once_in_parent_order() {
+        let parents = layered_parents();
+        let paths: Vec<Vec<u32>> = ParentPaths::new(&parents, 6, 0).collect();
+        assert_eq!(
+            paths,
+            vec![
+                vec![6, 4, 1, 0],
+                vec![6, 4, 2, 0],
+                vec![6, 4, 3, 0],
+                vec![6, 5, 1, 0],
+                vec![6, 5, 2, 0],
+                vec![6, 5, 3, 0],
+            ]
+        );
+        assert_eq!(count_parent_paths(6, 0, &parents, &mut FxHashMap::default()), 6);
+    }
+
+    #[test]
+    fn parent_paths_stop_at_the_limit() {
+        let parents = layered_parents();
+        let paths: Vec<Vec<u32>> = ParentPaths::new(&parents, 6, 0).take(2).collect();
+        assert_eq!(paths, vec![vec![6, 4, 1, 0], vec![6, 4, 2, 0]]);
+    }
+
+    #[test]
+    fn parent_paths_of_the_root() {
+        let parents = layered_parents();
+        assert_eq!(ParentPaths::new(&parents, 0, 0).collect::<Vec<_>>(), vec![vec![0]]);
+    }
+
+    #[test]
+    fn all_shortest_paths_of_a_diamond() {
+        // 0 -> {1, 2} -> 3, plus a longer detour 0 -> 4 -> 5 -> 3.
+        let bytes = graph_bytes(6, &[(0, 1), (0, 2), (1, 3), (2, 3), (0, 4), (4, 5), (5, 3)]);
+        let graph = archived(&bytes);
+        let ids = |path: &[u32]| path.iter().map(|&index| test_page_id(index)).collect::<Vec<_>>();
+
+        let all = find_all_shortest_path(graph, test_page_id(0), test_page_id(3), None);
+        let mut paths = all.paths.clone();
+        paths.sort_unstable();
+        assert_eq!(paths, vec![ids(&[0, 1, 3]), ids(&[0, 2, 3])]);
+        assert_eq!(all.num_paths, 2);
+        assert_eq!(all.path_length, 3);
+
+        let limited = find_all_shortest_path(graph, test_page_id(0), test_page_id(3), Some(1));
+        assert_eq!(limited.paths.len(), 1);
+        assert!(paths.contains(&limited.paths[0]));
+        // The count ignores the limit.
+        assert_eq!(limited.num_paths, 2);
+    }
+
+    #[test]
+    fn duplicated_links_are_counted_once() {
+        // The diamond 0 -> {1, 2} -> 3 with links repeated, as migrated graphs keep them.
+        let bytes = graph_bytes(4, &[(0, 1), (0, 2), (0, 1), (1, 3), (2, 3), (1, 3), (2, 3), (0, 2)]);
+        let graph = archived(&bytes);
+        let all = find_all_shortest_path(graph, test_page_id(0), test_page_id(3), None);
+        assert_eq!(all.paths.len(), 2);
+        assert_eq!(all.num_paths, 2);
+        let count = count_shortest_path(graph, test_page_id(0), test_page_id(3));
+        assert_eq!(count.num_paths, 2);
+        assert_eq!(count.path_length, 3);
+    }
+
+    /// Every shortest path from `start` to `end` found by a plain sequential
+    /// BFS from `start`, sorted.
+    fn reference_paths(node_count: u32, links: &[(u32, u32)], start: u32, end: u32) -> Vec<Vec<u32>> {
+        let mut dist = vec![u32::MAX; node_count as usize];
+        dist[start as usize] = 0;
+        let mut queue = std::collections::VecDeque::from([start]);
+        while let Some(u) = queue.pop_front() {
+            for &(from, to) in links {
+                if from == u && dist[to as usize] == u32::MAX {
+                    dist[to as usize] = dist[u as usize] + 1;
+                    queue.push_back(to);
+                }
+            }
+        }
+        if dist[end as usize] == u32::MAX {
+            return Vec::new();
+        }
+        // Walk back from `end` through every distinct link one level closer to `start`.
+        let mut links = links.to_vec();
+        links.sort_unstable();
+        links.dedup();
+        let mut paths = Vec::new();
+        let mut partial = vec![vec![end]];
+        while let Some(path) = partial.pop() {
+            let node = *path.last().unwrap();
+            if node == start {
+                paths.push(path.into_iter().rev().collect());
+                continue;
+            }
+            for &(from, to) in &links {
+                if to == node && dist[from as usize] != u32::MAX && dist[from as usize] + 1 == dist[node as usize] {
+                    let mut longer = path.clone();
+                    longer.push(from);
+                    partial.push(longer);
+                }
+            }
+        }
+        paths.sort_unstable();
+        paths
+    }
+
+    /// Runs the path and count searches with every level expanded top-down,
+    /// bottom-up, or as chosen by the search, and checks they all agree with
+    /// the reference BFS.
+    fn assert_expansions_agree(node_count: u32, links: &[(u32, u32)], start: u32, end: u32) {
+        let bytes = graph_bytes(node_count, links);
+        let graph = archived(&bytes);
+        let expected = reference_paths(node_count, links, start, end);
+
+        let mut outputs = Vec::new();
+        for expansion in [Expansion::Auto, Expansion::TopDown, Expansion::BottomUp] {
+            let search: Option<ShortestPathSearch> = bidirectional_bfs_with(graph, start, end, expansion);
+            let (paths, num_paths) = match &search {
+                Some(search) => (search.paths().collect::<Vec<_>>(), search.count_paths()),
+                None => (Vec::new(), 0),
+            };
+            let mut sorted = paths.clone();
+            sorted.sort_unstable();
+            assert_eq!(sorted, expected, "{:?} paths from {} to {}", expansion, start, end);
+            assert_eq!(num_paths, expected.len() as u128, "{:?} path count", expansion);
+
+            let count = count_paths_between(graph, start, end, expansion);
+            assert_eq!(count.num_paths, expected.len() as u128, "{:?} counted paths", expansion);
+            let length = expected.first().map_or(0, |path| path.len());
+            assert_eq!(count.path_length, length, "{:?} counted length", expansion);
+            outputs.push(paths);
+        }
+        // Parents are recorded identically, so even the enumeration order matches.
+        assert!(outputs.windows(2).all(|pair| pair[0] == pair[1]), "paths differ between expansions");
+    }
+
+    #[test]
+    fn expansions_agree_on_diamonds() {
+        // Two diamonds in a row, 0 -> {1, 2} -> 3 -> {4, 5} -> 6, with a detour 0 -> 7 -> 8 -> 9 -> 6.
+        let links = [(0, 1), (0, 2), (1, 3), (2, 3), (3, 4), (3, 5), (4, 6), (5, 6), (0, 7), (7, 8), (8, 9), (9, 6)];
+        assert_expansions_agree(10, &links, 0, 6);
+        assert_expansions_agree(10, &links, 0, 3);
+        assert_expansions_agree(10, &links, 1, 6);
+    }
+
+    #[test]
+    fn expansions_agree_on_multi_parent_layers() {
+        // Three layers fully linked to the next, with links between nodes of a
+        // layer and back to earlier layers that no shortest path can use.
+        let layers: [&[u32]; 5] = [&[0], &[1, 2, 3], &[4, 5, 6, 7], &[8, 9], &[10]];
+        let mut links = Vec::new();
+        for pair in layers.windows(2) {
+            for &from in pair[0] {
+                for &to in pair[1] {
+                    links.push((from, to));
+                }
+            }
+        }
+        links.extend([(2, 1), (5, 4), (8, 2), (10, 0), (9, 6), (6, 9)]);
+        assert_expansions_agree(11, &links, 0, 10);
+        assert_expansions_agree(11, &links, 10, 8);
+        assert_expansions_agree(11, &links, 3, 9);
+    }
+
+    #[test]
+    fn expansions_agree_on_frontiers_expanded_in_parallel() {
+        // A hub linking to more nodes than a sequential level expands, all leading to one target.
+        let fan_out = PARALLEL_MIN_FRONTIER as u32 + 500;
+        let target = fan_out + 1;
+        let mut links: Vec<(u32, u32)> = (1..=fan_out).map(|node| (0, node)).collect();
+        links.extend((1..=fan_out).map(|node| (node, target)));
+        links.extend((1..fan_out).map(|node| (node, node + 1)));
+        assert_expansions_agree(target + 1, &links, 0, target);
+    }
+
+    #[test]
+    fn expansions_agree_on_unreachable_targets() {
+        // 3 only links to 0 and 4 is isolated, the others form a cycle.
+        let links = [(0, 1), (1, 2), (2, 0), (3, 0)];
+        assert_expansions_agree(5, &links, 0, 3);
+        assert_expansions_agree(5, &links, 0, 4);
+        assert_expansions_agree(5, &links, 4, 0);
+    }
+
+    #[test]
+    fn expansions_agree_when_source_is_target() {
+        let links = [(0, 1), (1, 0), (1, 2)];
+        assert_expansions_agree(3, &links, 1, 1);
+        assert_expansions_agree(3, &links, 2, 2);
+    }
+}
@@ -0,0 +1,77 @@
+use std::sync::{LazyLock, Mutex};
+
+/// Array of values indexed by node that can be emptied in O(1): a value
+/// only counts when its stamp matches the current generation.
+#[derive(Default)]
+pub struct StampedArray<T = u32> {
+    generation: u32,
+    stamps: Vec<u32>,
+    values: Vec<T>,
+}
+
+impl<T: Copy + Default> StampedArray<T> {
+    /// Forget every value and make room for `len` nodes.
+    pub fn reset(&mut self, len: usize) {
+        if self.stamps.len() < len {
+            self.stamps.resize(len, 0);
+            self.values.resize(len, T::default());
+        }
+        self.generation = self.generation.wrapping_add(1);
+        if self.generation == 0 {
+            // Stamps from 2^32 searches ago would look current again.
+            self.stamps.fill(0);
+            self.generation = 1;
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, node: u32) -> Option<T> {
+        if self.stamps[node as usize] == self.generation {
+            Some(self.values[node as usize])
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn contains(&self, node: u32) -> bool {
+        self.stamps[node as usize] == self.generation
+    }
+
+    #[inline]
+    pub fn set(&mut self, node: u32, value: T) {
+        self.stamps[node as usize] = self.generation;
+        self.values[node as usize] = value;
+    }
+}
+
+/// Per node state of both directions of a search, sized to the node count of
+/// the graph and reused across requests instead of being allocated per query.
+#[derive(Default)]
+pub struct SearchWorkspace {
+    pub forward: StampedArray,
+    pub backward: StampedArray,
+    /// Path counts of both directions, only sized and reset by the searches counting paths.
+    pub forward_counts: StampedArray<u128>,
+    pub backward_counts: StampedArray<u128>,
+}
+
+// Workspaces not currently used by a search. At most one per CPU is kept, as
+// more searches than CPUs only run at once in bursts, and a workspace left by
+// one is dropped rather than held by an idle blocking thread.
+static WORKSPACE_POOL: Mutex<Vec<SearchWorkspace>> = Mutex::new(Vec::new());
+static MAX_POOLED_WORKSPACES: LazyLock<usize> = LazyLock::new(num_cpus::get);
+
+/// Run `f` with an empty workspace for a graph of `node_count` nodes, taken
+/// from the pool and given back once `f` returns unless the pool is full.
+pub fn with_workspace<T>(node_count: usize, f: impl FnOnce(&mut SearchWorkspace) -> T) -> T {
+    let mut workspace = WORKSPACE_POOL.lock().unwrap().pop().unwrap_or_default();
+    workspace.forward.reset(node_count);
+    workspace.backward.reset(node_count);
+    let result = f(&mut workspace);
+    let mut pool = WORKSPACE_POOL.lock().unwrap();
+    if pool.len() < *MAX_POOLED_WORKSPACES {
+        pool.push(workspace);
+    }
+    result
+}
@@ -0,0 +1,115 @@
+//! Throughput of the SQL dump tokenizer on generated page and pagelinks dumps.
+//!
+//! Run with `cargo bench --bench sql_tokenizer`.
+
+#[allow(dead_code)]
+#[path = "../src/dump/sql_tokenizer.rs"]
+mod sql_tokenizer;
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::XorShift;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use sql_tokenizer::{column_indices, SqlTokenizer};
+use std::hint::black_box;
+
+const ROW_COUNT: u32 = 500_000;
+const ROWS_PER_STATEMENT: u32 = 2_000;
+
+/// A dump laid out like mysqldump does, `row` writing the fields of each tuple.
+fn generate_dump(table: &str, columns: &[&str], mut row: impl FnMut(u32, &mut String)) -> Vec<u8> {
+    let mut dump = format!("-- MySQL dump 10.19\nCREATE TABLE `{}` (\n", table);
+    for column in columns {
+        dump.push_str(&format!("  `{}` int(10) unsigned NOT NULL,\n", column));
+    }
+    dump.push_str(") ENGINE=InnoDB DEFAULT CHARSET=binary;\n");
+    for index in 0..ROW_COUNT {
+        if index.is_multiple_of(ROWS_PER_STATEMENT) {
+            dump.push_str(&format!("INSERT INTO `{}` VALUES (", table));
+        } else {
+            dump.push_str(",(");
+        }
+        row(index, &mut dump);
+        dump.push(')');
+        if index % ROWS_PER_STATEMENT == ROWS_PER_STATEMENT - 1 || index == ROW_COUNT - 1 {
+            dump.push_str(";\n");
+        }
+    }
+    dump.into_bytes()
+}
+
+fn page_dump(rng: &mut XorShift) -> Vec<u8> {
+    let columns = ["page_id", "page_namespace", "page_title", "page_is_redirect", "page_len"];
+    generate_dump("page", &columns, |index, dump| {
+        let namespace = [0, 0, 0, 1, 4, 14][rng.next() as usize % 6];
+        let quote = if rng.next().is_multiple_of(50) { "\\'" } else { "" };
+        dump.push_str(&format!(
+            "{},{},'Title_{}{}_{}',{},{}",
+            index * 3 + 1,
+            namespace,
+            rng.next() % 100_000,
+            quote,
+            index,
+            rng.next() % 2,
+            rng.next() % 200_000
+        ));
+    })
+}
+
+fn pagelinks_dump(rng: &mut XorShift) -> Vec<u8> {
+    let columns = ["pl_from", "pl_from_namespace", "pl_target_id"];
+    generate_dump("pagelinks", &columns, |index, dump| {
+        dump.push_str(&format!("{},0,{}", index / 8 * 3 + 1, rng.next() % 10_000_000));
+    })
+}
+
+/// Reads the id and the title of every page in the main namespace.
+fn tokenize_pages(dump: &[u8]) -> u64 {
+    let mut tokenizer = SqlTokenizer::new(dump);
+    let columns = tokenizer.read_columns().unwrap();
+    let [page_id, page_namespace, page_title] =
+        column_indices(&columns, ["page_id", "page_namespace", "page_title"]).unwrap();
+    let mut checksum = 0;
+    while let Some(tuple) = tokenizer.next_tuple().unwrap() {
+        if tuple.get(page_namespace).unwrap().to_u32() != Some(0) {
+            continue;
+        }
+        let id = tuple.get(page_id).unwrap().to_u32().unwrap();
+        let title = tuple.get(page_title).unwrap().to_str().unwrap();
+        checksum += id as u64 + title.len() as u64;
+    }
+    checksum
+}
+
+/// Reads the source and the target of every link.
+fn tokenize_pagelinks(dump: &[u8]) -> u64 {
+    let mut tokenizer = SqlTokenizer::new(dump);
+    let columns = tokenizer.read_columns().unwrap();
+    let [pl_from, pl_target_id] = column_indices(&columns, ["pl_from", "pl_target_id"]).unwrap();
+    let mut checksum = 0;
+    while let Some(tuple) = tokenizer.next_tuple().unwrap() {
+        checksum += tuple.get(pl_from).unwrap().to_u32().unwrap() as u64;
+        checksum += tuple.get(pl_target_id).unwrap().to_u32().unwrap() as u64;
+    }
+    checksum
+}
+
+fn tokenizer_benchmark(c: &mut Criterion) {
+    let mut rng = XorShift(0x5eed_cafe_f00d_beef);
+    let page = page_dump(&mut rng);
+    let pagelinks = pagelinks_dump(&mut rng);
+
+    let mut group = c.benchmark_group("sql_tokenizer");
+    group.throughput(Throughput::Bytes(page.len() as u64));
+    group.bench_function("page", |b| b.iter(|| black_box(tokenize_pages(black_box(&page)))));
+    group.throughput(Throughput::Bytes(pagelinks.len() as u64));
+    group.bench_function("pagelinks", |b| b.iter(|| black_box(tokenize_pagelinks(black_box(&pagelinks)))));
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = tokenizer_benchmark
+}
+criterion_main!(benches);
@@ -0,0 +1,23 @@
+use std::path::Path;
+use std::process::Command;
+
+// Expose the git revision as BUILDER_GIT_VERSION so graphs record which builder produced them.
+fn main() {
+    let git_version = Command::new("git")
+        .args(["describe", "--always", "--dirty"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|version| version.trim().to_string())
+        .filter(|version| !version.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=BUILDER_GIT_VERSION={}", git_version);
+
+    for git_file in ["../.git/HEAD", "../.git/index"] {
+        if Path::new(git_file).exists() {
+            println!("cargo:rerun-if-changed={}", git_file);
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,226 @@
+use std::path::{Path, PathBuf};
+use crate::reorder::NodeOrder;
+use crate::PHASES;
+
+pub const USAGE: &str = "\
+Usage: sql-dump-to-rust [COMMAND] [OPTIONS]
+
+Commands:
+  download          Download the dumps into the cache
+  parse             Download and parse the dumps into phase checkpoints
+  build             Download, parse and build the graph (default)
+  inspect [GRAPH]   Print the metadata of a graph, --output by default
+
+Options (environment variable used when not given):
+  --lang LANG            Wiki language, `en` for enwiki (WIKI_LANG)
+  --date DATE            Dump date as YYYYMMDD, or latest (DUMP_DATE, latest)
+  --mirror URL           Base URL of the dumps, file:// for a local mirror (DUMP_MIRROR, https://dumps.wikimedia.org)
+  --cache-dir DIR        Where dumps and checkpoints are kept (CACHE_DIR, cache)
+  --output PATH          Graph file to write (GRAPH_OUTPUT, graph.rkyv)
+  --threads N            Parser threads per dump (THREADS, available cores)
+  --multithread          Parse every dump in parallel from its gunzipped file, which replaces the
+                         downloaded one in the cache (USE_MULTITHREAD)
+  --compress             Write compressed adjacency lists (COMPRESS_ADJACENCY)
+  --reorder ORDER        none, degree or bfs node order (REORDER_NODES, none)
+  --write-maps           Save the dump tables next to the graph (WRITE_MAPS)
+  --reuse-from GRAPH     Reuse the tables of a graph built with --write-maps for the dumps that did not
+                         change, parse the others whole, and report the changes to GRAPH (REUSE_FROM)
+  --from-phase PHASE     Run PHASE and the later ones again despite their checkpoints (FROM_PHASE)
+  --page-dump PATH       Local page dump, gzipped or plain (PAGE_DUMP)
+  --redirect-dump PATH   Local redirect dump (REDIRECT_DUMP)
+  --linktarget-dump PATH Local linktarget dump (LINKTARGET_DUMP)
+  --pagelinks-dump PATH  Local pagelinks dump (PAGELINKS_DUMP)
+  --download-retries N   Failed requests in a row before a download gives up (DOWNLOAD_RETRIES, 5)
+  --offline              Fail instead of downloading a dump that is neither local nor cached (OFFLINE)
+  --allow-unverified     Use downloaded dumps without a published checksum to verify, local dumps
+                         are never verified (ALLOW_UNVERIFIED)
+  -h, --help             Print this help";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Download,
+    Parse,
+    Build,
+    Inspect,
+    Help,
+}
+
+#[derive(Debug)]
+pub struct Config {
+    pub command: Command,
+    pub lang: String,
+    /// Dump date as `YYYYMMDD`, or `latest`.
+    pub date: String,
+    /// Base URL of the dumps, without a trailing slash.
+    pub mirror: String,
+    pub cache_dir: PathBuf,
+    /// Graph written by `build`, or read by `inspect`.
+    pub graph_path: PathBuf,
+    pub threads: usize,
+    pub multithread: bool,
+    pub compress: bool,
+    pub node_order: NodeOrder,
+    pub write_maps: bool,
+    pub reuse_from: Option<PathBuf>,
+    /// Index in `PHASES` of the first phase run despite its checkpoint.
+    pub from_phase: Option<usize>,
+    /// Dumps read from local files instead of the cache, by file type.
+    pub local_dumps: Vec<(&'static str, PathBuf)>,
+    /// Failed requests in a row before a download gives up, retried with exponential backoff.
+    pub download_retries: u32,
+    pub offline: bool,
+    /// Whether downloaded dumps may be used when their checksum cannot be verified.
+    pub allow_unverified: bool,
+}
+
+/// Options taking a value, with the environment variable used when they are not given.
+const OPTIONS: [(&str, &str); 14] = [
+    ("--lang", "WIKI_LANG"),
+    ("--date", "DUMP_DATE"),
+    ("--mirror", "DUMP_MIRROR"),
+    ("--cache-dir", "CACHE_DIR"),
+    ("--output", "GRAPH_OUTPUT"),
+    ("--threads", "THREADS"),
+    ("--reorder", "REORDER_NODES"),
+    ("--reuse-from", "REUSE_FROM"),
+    ("--from-phase", "FROM_PHASE"),
+    ("--page-dump", "PAGE_DUMP"),
+    ("--redirect-dump", "REDIRECT_DUMP"),
+    ("--linktarget-dump", "LINKTARGET_DUMP"),
+    ("--pagelinks-dump", "PAGELINKS_DUMP"),
+    ("--download-retries", "DOWNLOAD_RETRIES"),
+];
+/// Options without a value, enabled by `1` or `true` in their environment variable.
+const FLAGS: [(&str, &str); 5] = [
+    ("--multithread", "USE_MULTITHREAD"),
+    ("--compress", "COMPRESS_ADJACENCY"),
+    ("--write-maps", "WRITE_MAPS"),
+    ("--offline", "OFFLINE"),
+    ("--allow-unverified", "ALLOW_UNVERIFIED"),
+];
+
+impl Config {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut values: Vec<(&str, String)> = Vec::new();
+        let mut positionals = Vec::new();
+        let mut help = false;
+        while let Some(arg) = args.next() {
+            if arg == "-h" || arg == "--help" {
+                help = true;
+                break;
+            }
+            if !arg.starts_with("--") {
+                positionals.push(arg);
+                continue;
+            }
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (arg.as_str(), None),
+            };
+            if let Some((option, _)) = OPTIONS.iter().find(|(option, _)| *option == name) {
+                let value = match inline_value {
+                    Some(value) => value,
+                    None => args.next().ok_or_else(|| format!("{} needs a value", option))?,
+                };
+                values.push((option, value));
+            } else if let Some((flag, _)) = FLAGS.iter().find(|(flag, _)| *flag == name) {
+                values.push((flag, inline_value.unwrap_or_else(|| "1".to_string())));
+            } else {
+                return Err(format!("Unknown option {}", name));
+            }
+        }
+
+        let mut positionals = positionals.into_iter();
+        let command = match positionals.next().as_deref() {
+            _ if help => Command::Help,
+            None | Some("build") => Command::Build,
+            Some("download") => Command::Download,
+            Some("parse") => Command::Parse,
+            Some("inspect") => Command::Inspect,
+            Some("help") => Command::Help,
+            Some(other) => return Err(format!("Unknown command {}", other)),
+        };
+        let inspected_graph = if command == Command::Inspect { positionals.next() } else { None };
+        if let Some(extra) = positionals.next().filter(|_| command != Command::Help) {
+            return Err(format!("Unexpected argument {}", extra));
+        }
+
+        let value = |name: &str| -> Option<String> {
+            let env_name = OPTIONS.iter().chain(FLAGS.iter()).find(|(option, _)| *option == name).map(|(_, env_name)| env_name)?;
+            values
+                .iter()
+                .rev()
+                .find(|(option, _)| *option == name)
+                .map(|(_, value)| value.clone())
+                .or_else(|| std::env::var(env_name).ok())
+                .filter(|value| !value.is_empty())
+        };
+        let flag = |name: &str| value(name).is_some_and(|value| value == "1" || value == "true");
+
+        let lang = match value("--lang") {
+            Some(lang) => lang,
+            // Inspecting a graph does not need to know its language beforehand.
+            None if matches!(command, Command::Inspect | Command::Help) => String::new(),
+            None => return Err("The wiki language must be given with --lang or WIKI_LANG".to_string()),
+        };
+        let date = value("--date").unwrap_or_else(|| "latest".to_string());
+        if date != "latest" && !(date.len() == 8 && date.bytes().all(|byte| byte.is_ascii_digit())) {
+            return Err(format!("Invalid dump date {}, expected YYYYMMDD or latest", date));
+        }
+        let threads = match value("--threads") {
+            Some(threads) => threads
+                .parse()
+                .ok()
+                .filter(|threads| *threads > 0)
+                .ok_or_else(|| format!("Invalid thread count {}", threads))?,
+            None => std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+        };
+        let download_retries = match value("--download-retries") {
+            Some(retries) => retries.parse().map_err(|_| format!("Invalid retry count {}", retries))?,
+            None => 5,
+        };
+        let node_order = match value("--reorder") {
+            Some(order) => NodeOrder::parse(&order).ok_or_else(|| format!("Unknown node order {}, expected none, degree or bfs", order))?,
+            None => NodeOrder::PageId,
+        };
+        let from_phase = match value("--from-phase") {
+            Some(phase) => Some(
+                PHASES
+                    .iter()
+                    .position(|known_phase| *known_phase == phase)
+                    .ok_or_else(|| format!("Unknown phase {}, expected one of {}", phase, PHASES.join(", ")))?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            command,
+            lang,
+            date,
+            mirror: value("--mirror")
+                .unwrap_or_else(|| "https://dumps.wikimedia.org".to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            cache_dir: value("--cache-dir").unwrap_or_else(|| "cache".to_string()).into(),
+            graph_path: inspected_graph.or_else(|| value("--output")).unwrap_or_else(|| "graph.rkyv".to_string()).into(),
+            threads,
+            multithread: flag("--multithread"),
+            compress: flag("--compress"),
+            node_order,
+            write_maps: flag("--write-maps"),
+            reuse_from: value("--reuse-from").map(PathBuf::from),
+            from_phase,
+            local_dumps: PHASES
+                .iter()
+                .filter_map(|file_type| Some((*file_type, value(&format!("--{}-dump", file_type))?.into())))
+                .collect(),
+            download_retries,
+            offline: flag("--offline"),
+            allow_unverified: flag("--allow-unverified"),
+        })
+    }
+
+    pub fn local_dump(&self, file_type: &str) -> Option<&Path> {
+        self.local_dumps.iter().find(|(local_file_type, _)| *local_file_type == file_type).map(|(_, path)| path.as_path())
+    }
+}
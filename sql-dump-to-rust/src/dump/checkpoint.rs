@@ -0,0 +1,166 @@
+use rkyv::api::high::{HighSerializer, HighValidator};
+use rkyv::bytecheck::CheckBytes;
+use rkyv::de::Pool;
+use rkyv::rancor::{Error, Strategy};
+use rkyv::ser::allocator::ArenaHandle;
+use rkyv::util::AlignedVec;
+use rkyv::{Archive, Deserialize, Serialize};
+use rustc_hash::FxHasher;
+use std::hash::Hasher;
+use std::time::UNIX_EPOCH;
+use std::{fs::File, io::Write, path::PathBuf};
+use crate::dump_checksum::DumpChecksum;
+use crate::dump_headers::DumpHeaders;
+
+/// Start of every checkpoint file, followed by the format version and the dumps key.
+const CHECKPOINT_MAGIC: [u8; 8] = *b"WIKICKPT";
+/// Bumped whenever what a phase stores in its checkpoint or how the dumps are keyed changes.
+const CHECKPOINT_FORMAT_VERSION: u32 = 2;
+/// Magic, format version, padding, dumps key, then padding keeping the archive 16-byte aligned.
+const CHECKPOINT_HEADER_LEN: usize = 32;
+
+/// What a phase is parsed from.
+pub enum CheckpointInput {
+    /// A dump in the cache or given locally.
+    Dump(PathBuf),
+    /// The table of a dump reused from a previous build, whose dump may not
+    /// be on disk, told apart by the headers of that dump.
+    ReusedTable(DumpHeaders),
+}
+
+/// The output of one parse phase, valid as long as the dumps it was parsed
+/// from are the same.
+pub struct Checkpoint {
+    path: PathBuf,
+    inputs: Vec<CheckpointInput>,
+    key: Option<u64>,
+}
+
+impl Checkpoint {
+    pub fn new(path: PathBuf, inputs: Vec<CheckpointInput>) -> Self {
+        Self { path, inputs, key: None }
+    }
+
+    /// Fingerprint of which dumps these are, `None` while one is not downloaded.
+    ///
+    /// A dump is told apart by the checksum it was verified against, or by
+    /// its path, size and modification time when it was never verified, so
+    /// no dump is read. The fingerprint only serves to notice that a dump
+    /// changed, it is no hash of the dumps' content.
+    fn key(&mut self) -> std::io::Result<Option<u64>> {
+        if self.key.is_none() {
+            if self.inputs.iter().any(|input| matches!(input, CheckpointInput::Dump(dump_path) if !dump_path.exists())) {
+                return Ok(None);
+            }
+            let mut hasher = FxHasher::default();
+            for input in &self.inputs {
+                let dump_path = match input {
+                    CheckpointInput::Dump(dump_path) => dump_path,
+                    CheckpointInput::ReusedTable(headers) => {
+                        hasher.write_u8(2);
+                        for header in [&headers.etag, &headers.last_modified] {
+                            hasher.write_u8(header.is_some() as u8);
+                            hasher.write(header.as_deref().unwrap_or_default().as_bytes());
+                        }
+                        continue;
+                    }
+                };
+                match DumpChecksum::load_verified(dump_path) {
+                    Some(checksum) => {
+                        hasher.write_u8(0);
+                        hasher.write(checksum.algorithm.name().as_bytes());
+                        hasher.write(checksum.hex.as_bytes());
+                    }
+                    None => {
+                        let metadata = std::fs::metadata(dump_path)?;
+                        let modified = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+                        hasher.write_u8(1);
+                        hasher.write(dump_path.as_os_str().as_encoded_bytes());
+                        hasher.write_u64(metadata.len());
+                        hasher.write_u128(modified.as_nanos());
+                    }
+                }
+            }
+            self.key = Some(hasher.finish());
+        }
+        Ok(self.key)
+    }
+
+    /// The phase output saved for the same dumps, `None` when there is none.
+    pub fn load<T>(&mut self) -> Option<T>
+    where
+        T: Archive,
+        T::Archived: for<'a> CheckBytes<HighValidator<'a, Error>> + Deserialize<T, Strategy<Pool, Error>>,
+    {
+        let key = match self.key() {
+            Ok(key) => key?,
+            Err(err) => {
+                println!("Failed to read the dumps of {}: {}", self.path.display(), err);
+                return None;
+            }
+        };
+        let file = File::open(&self.path).ok()?;
+        // SAFETY: the file is only read, while nothing else is expected to write it.
+        let mmap = unsafe { memmap2::Mmap::map(&file).ok()? };
+        if mmap.len() < CHECKPOINT_HEADER_LEN
+            || mmap[..CHECKPOINT_MAGIC.len()] != CHECKPOINT_MAGIC
+            || mmap[8..12] != CHECKPOINT_FORMAT_VERSION.to_le_bytes()
+            || mmap[16..24] != key.to_le_bytes()
+        {
+            println!("Checkpoint {} is outdated", self.path.display());
+            return None;
+        }
+        match rkyv::from_bytes::<T, Error>(&mmap[CHECKPOINT_HEADER_LEN..]) {
+            Ok(value) => Some(value),
+            Err(err) => {
+                println!("Checkpoint {} is corrupted: {}", self.path.display(), err);
+                None
+            }
+        }
+    }
+
+    /// `load` run on the blocking thread pool, for the tasks of the phases.
+    /// Nothing is loaded when `skip`, and the checkpoint is handed back to
+    /// save the phase output with when the phase runs.
+    pub async fn load_blocking<T>(mut self, skip: bool) -> (Self, Option<T>)
+    where
+        T: Archive + Send + 'static,
+        T::Archived: for<'a> CheckBytes<HighValidator<'a, Error>> + Deserialize<T, Strategy<Pool, Error>>,
+    {
+        if skip {
+            return (self, None);
+        }
+        tokio::task::spawn_blocking(move || {
+            let value = self.load();
+            (self, value)
+        })
+        .await
+        .expect("Loading a checkpoint panicked")
+    }
+
+    /// Saves the phase output for the current dumps, going through a
+    /// temporary file so a crash never leaves a truncated checkpoint.
+    pub fn save<T>(&mut self, value: &T) -> Result<(), Box<dyn std::error::Error>>
+    where
+        T: for<'a> Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, Error>>,
+    {
+        let key = self.key()?.ok_or("Dumps must be downloaded before saving their checkpoint")?;
+        let bytes = rkyv::to_bytes::<Error>(value)?;
+        let mut header = [0u8; CHECKPOINT_HEADER_LEN];
+        header[..CHECKPOINT_MAGIC.len()].copy_from_slice(&CHECKPOINT_MAGIC);
+        header[8..12].copy_from_slice(&CHECKPOINT_FORMAT_VERSION.to_le_bytes());
+        header[16..24].copy_from_slice(&key.to_le_bytes());
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut temporary_path = self.path.as_os_str().to_owned();
+        temporary_path.push(".part");
+        let mut file = File::create(&temporary_path)?;
+        file.write_all(&header)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        std::fs::rename(&temporary_path, &self.path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+use md5::Md5;
+use sha1::{Digest, Sha1};
+use std::{fs::File, io::Read, path::{Path, PathBuf}};
+
+/// Algorithms of the checksum files published next to the dumps, preferred first.
+const ALGORITHMS: [ChecksumAlgorithm; 2] = [ChecksumAlgorithm::Sha1, ChecksumAlgorithm::Md5];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChecksumAlgorithm {
+    Sha1,
+    Md5,
+}
+
+impl ChecksumAlgorithm {
+    pub fn name(self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Sha1 => "sha1",
+            ChecksumAlgorithm::Md5 => "md5",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        ALGORITHMS.into_iter().find(|algorithm| algorithm.name() == name)
+    }
+}
+
+/// Checksum of a dump as published, or as verified for a cached dump.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DumpChecksum {
+    pub algorithm: ChecksumAlgorithm,
+    pub hex: String,
+}
+
+impl DumpChecksum {
+    /// Published checksum of the `{file_type}.sql.gz` dump, read from the
+    /// `{prefix}-sha1sums.txt` or `{prefix}-md5sums.txt` file at `base_url`.
+    /// `None` when neither lists it.
+    pub async fn fetch(base_url: &str, prefix: &str, file_type: &str) -> Option<Self> {
+        let client = reqwest::Client::new();
+        let suffix = format!("-{}.sql.gz", file_type);
+        for algorithm in ALGORITHMS {
+            let url = format!("{}/{}-{}sums.txt", base_url, prefix, algorithm.name());
+            let Ok(res) = client.get(&url).send().await.and_then(|res| res.error_for_status()) else {
+                continue;
+            };
+            let Ok(content) = res.text().await else {
+                continue;
+            };
+            // Lines are `<hex>  <file name>`, with dated file names even in `latest`.
+            let checksum = content.lines().find_map(|line| {
+                let (hex, file_name) = line.split_once(char::is_whitespace)?;
+                file_name.trim().ends_with(&suffix).then(|| DumpChecksum { algorithm, hex: hex.to_ascii_lowercase() })
+            });
+            if checksum.is_some() {
+                return checksum;
+            }
+        }
+        None
+    }
+
+    /// Checksum of `path` with the same algorithm.
+    pub fn of_file(&self, path: &Path) -> std::io::Result<Self> {
+        let mut hasher = DumpHasher::new(self.algorithm);
+        let mut file = File::open(path)?;
+        let mut buffer = vec![0u8; 1 << 20];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                return Ok(hasher.finish());
+            }
+            hasher.update(&buffer[..read]);
+        }
+    }
+
+    fn sidecar_path(dump_path: &Path) -> PathBuf {
+        let mut path = dump_path.as_os_str().to_owned();
+        path.push(".checksum");
+        PathBuf::from(path)
+    }
+
+    /// Records that `dump_path` matched this checksum, along with its size.
+    pub fn save_verified(&self, dump_path: &Path) -> std::io::Result<()> {
+        let size = std::fs::metadata(dump_path)?.len();
+        std::fs::write(Self::sidecar_path(dump_path), format!("{} {} {}\n", self.algorithm.name(), self.hex, size))
+    }
+
+    /// Checksum `dump_path` was verified against, as long as it kept the same size.
+    pub fn load_verified(dump_path: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(Self::sidecar_path(dump_path)).ok()?;
+        let mut fields = content.split_whitespace();
+        let algorithm = ChecksumAlgorithm::from_name(fields.next()?)?;
+        let hex = fields.next()?.to_string();
+        let size: u64 = fields.next()?.parse().ok()?;
+        (std::fs::metadata(dump_path).ok()?.len() == size).then_some(DumpChecksum { algorithm, hex })
+    }
+
+    pub fn remove_verified(dump_path: &Path) {
+        let _ = std::fs::remove_file(Self::sidecar_path(dump_path));
+    }
+}
+
+/// Hashes a dump as it is read or downloaded.
+pub enum DumpHasher {
+    Sha1(Sha1),
+    Md5(Md5),
+}
+
+impl DumpHasher {
+    pub fn new(algorithm: ChecksumAlgorithm) -> Self {
+        match algorithm {
+            ChecksumAlgorithm::Sha1 => DumpHasher::Sha1(Sha1::new()),
+            ChecksumAlgorithm::Md5 => DumpHasher::Md5(Md5::new()),
+        }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        match self {
+            DumpHasher::Sha1(hasher) => hasher.update(bytes),
+            DumpHasher::Md5(hasher) => hasher.update(bytes),
+        }
+    }
+
+    pub fn finish(self) -> DumpChecksum {
+        let (algorithm, digest) = match self {
+            DumpHasher::Sha1(hasher) => (ChecksumAlgorithm::Sha1, hasher.finalize().to_vec()),
+            DumpHasher::Md5(hasher) => (ChecksumAlgorithm::Md5, hasher.finalize().to_vec()),
+        };
+        DumpChecksum { algorithm, hex: digest.iter().map(|byte| format!("{:02x}", byte)).collect() }
+    }
+}
@@ -0,0 +1,148 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::header::{CONTENT_RANGE, IF_RANGE, RANGE};
+use reqwest::{Client, StatusCode};
+use std::{fs::{File, OpenOptions}, io::Write, path::{Path, PathBuf}, time::{Duration, Instant}};
+use crate::dump_headers::DumpHeaders;
+
+/// Wait before the first retry, doubled after every failed request in a row.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+enum DownloadError {
+    /// The request failed in a way worth retrying, after receiving some bytes or not.
+    Retry { reason: String, progressed: bool },
+    Fatal(Box<dyn std::error::Error>),
+}
+
+fn part_path(path: &Path) -> PathBuf {
+    let mut part_path = path.as_os_str().to_owned();
+    part_path.push(".part");
+    PathBuf::from(part_path)
+}
+
+/// Downloads `url` to `path` through a `.part` file, renamed once complete.
+///
+/// The `.part` file is resumed with a Range request, after a dropped
+/// connection as after an interrupted run, as long as the server still
+/// serves the same dump. Failed requests are retried with exponential
+/// backoff, giving up after `max_retries` of them in a row.
+pub async fn download_resumable(url: &str, path: &Path, max_retries: u32) -> Result<DumpHeaders, Box<dyn std::error::Error>> {
+    let part_path = part_path(path);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let client = Client::new();
+    let mut failures = 0;
+    loop {
+        let (reason, progressed) = match download_part(&client, url, &part_path).await {
+            Ok(headers) => {
+                std::fs::rename(&part_path, path)?;
+                DumpHeaders::remove(&part_path);
+                return Ok(headers);
+            }
+            Err(DownloadError::Fatal(err)) => return Err(err),
+            Err(DownloadError::Retry { reason, progressed }) => (reason, progressed),
+        };
+        // Only failures without any progress in between count towards giving up.
+        failures = if progressed { 1 } else { failures + 1 };
+        if failures > max_retries {
+            return Err(format!("Failed to download {} after {} retries: {}", url, max_retries, reason).into());
+        }
+        let backoff = INITIAL_BACKOFF.saturating_mul(1 << (failures - 1).min(16)).min(MAX_BACKOFF);
+        println!("Downloading {} failed: {}, retrying in {}s", url, reason, backoff.as_secs());
+        tokio::time::sleep(backoff).await;
+    }
+}
+
+/// Downloads what is missing from `part_path`, from the start when it cannot be resumed.
+async fn download_part(client: &Client, url: &str, part_path: &Path) -> Result<DumpHeaders, DownloadError> {
+    let retry = |progressed: bool| move |err: reqwest::Error| DownloadError::Retry { reason: err.to_string(), progressed };
+    let fatal = |err: std::io::Error| DownloadError::Fatal(err.into());
+
+    let part_len = std::fs::metadata(part_path).map_or(0, |metadata| metadata.len());
+    let part_headers = DumpHeaders::load_saved(part_path);
+    let mut request = client.get(url);
+    // Without a validator the server could resume a newer dump, so the part is downloaded again.
+    let resume_from = match part_headers.as_ref().and_then(DumpHeaders::if_range) {
+        Some(if_range) if part_len > 0 => {
+            request = request.header(RANGE, format!("bytes={}-", part_len)).header(IF_RANGE, if_range);
+            part_len
+        }
+        _ => 0,
+    };
+
+    let mut res = request.send().await.map_err(retry(false))?;
+    let status = res.status();
+    if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::REQUEST_TIMEOUT {
+        return Err(DownloadError::Retry { reason: format!("HTTP status {}", status), progressed: false });
+    }
+    if status == StatusCode::RANGE_NOT_SATISFIABLE {
+        // The part is at least as long as the dump, it cannot be trusted.
+        let _ = std::fs::remove_file(part_path);
+        return Err(DownloadError::Retry { reason: "the partial download is longer than the dump".to_string(), progressed: false });
+    }
+    if let Err(err) = res.error_for_status_ref() {
+        return Err(DownloadError::Fatal(err.into()));
+    }
+
+    let headers = DumpHeaders::from_response(&res);
+    let resumed = status == StatusCode::PARTIAL_CONTENT;
+    if resumed {
+        let range_start = res
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("bytes "))
+            .and_then(|value| value.split('-').next())
+            .and_then(|start| start.parse::<u64>().ok());
+        if range_start != Some(resume_from) {
+            let _ = std::fs::remove_file(part_path);
+            return Err(DownloadError::Retry { reason: "the server resumed at the wrong offset".to_string(), progressed: false });
+        }
+        println!("Resuming {} from byte {}", url, resume_from);
+    }
+    let mut file = if resumed {
+        OpenOptions::new().append(true).open(part_path).map_err(fatal)?
+    } else {
+        let file = File::create(part_path).map_err(fatal)?;
+        headers.save(part_path).map_err(fatal)?;
+        file
+    };
+
+    let start = if resumed { resume_from } else { 0 };
+    let content_length = res.content_length();
+    let total_size = start + content_length.unwrap_or(0);
+    let pb = ProgressBar::new(total_size);
+    pb.set_style(ProgressStyle::default_bar()
+        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+        .unwrap()
+        .progress_chars("#>-"));
+    pb.set_position(start);
+
+    let mut received: u64 = 0;
+    let mut last_log_time = Instant::now();
+    let log_interval = Duration::from_secs(10);
+    // Note: `res` must be mutable to be read from.
+    while let Some(chunk) = res.chunk().await.map_err(|err| retry(received > 0)(err))? {
+        file.write_all(&chunk).map_err(fatal)?;
+        received += chunk.len() as u64;
+        pb.set_position(start + received);
+
+        if last_log_time.elapsed() >= log_interval {
+            println!(
+                "Downloading {}: {}/{} bytes ({:.2}%)",
+                url,
+                start + received,
+                total_size,
+                ((start + received) as f64 / total_size as f64) * 100.0
+            );
+            last_log_time = Instant::now();
+        }
+    }
+    if content_length.is_some_and(|content_length| received < content_length) {
+        return Err(DownloadError::Retry { reason: "the connection closed early".to_string(), progressed: received > 0 });
+    }
+    file.sync_all().map_err(fatal)?;
+    pb.finish_with_message("Downloaded");
+    Ok(headers)
+}
@@ -0,0 +1,95 @@
+use reqwest::Response;
+use reqwest::header::{ETAG, LAST_MODIFIED};
+use std::path::{Path, PathBuf};
+
+/// `ETag` and `Last-Modified` of a downloaded dump, kept next to the cached
+/// file so a graph built from the cache still knows which dump it came from.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DumpHeaders {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl DumpHeaders {
+    pub fn from_response(res: &Response) -> Self {
+        let header = |name| res.headers().get(name).and_then(|value| value.to_str().ok()).map(str::to_owned);
+        Self {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        }
+    }
+
+    /// Headers of the dump at `url` as currently published, without downloading it.
+    pub async fn fetch(url: &str) -> reqwest::Result<Self> {
+        let res = reqwest::Client::new().head(url).send().await?.error_for_status()?;
+        Ok(Self::from_response(&res))
+    }
+
+    /// Whether both headers identify the same dump. Headers without an
+    /// `ETag` or `Last-Modified` never match.
+    pub fn same_dump(&self, other: &Self) -> bool {
+        match (&self.etag, &other.etag) {
+            (Some(etag), Some(other_etag)) => etag == other_etag,
+            _ => self.last_modified.is_some() && self.last_modified == other.last_modified,
+        }
+    }
+
+    /// Validator for an `If-Range` request resuming the download of this dump.
+    /// Weak `ETag`s cannot be used to resume.
+    pub fn if_range(&self) -> Option<&str> {
+        match &self.etag {
+            Some(etag) if !etag.starts_with("W/") => Some(etag),
+            _ => self.last_modified.as_deref(),
+        }
+    }
+
+    fn sidecar_path(dump_path: &Path) -> PathBuf {
+        let mut path = dump_path.as_os_str().to_owned();
+        path.push(".headers");
+        PathBuf::from(path)
+    }
+
+    pub fn save(&self, dump_path: &Path) -> std::io::Result<()> {
+        let mut content = String::new();
+        if let Some(etag) = &self.etag {
+            content.push_str(&format!("etag: {}\n", etag));
+        }
+        if let Some(last_modified) = &self.last_modified {
+            content.push_str(&format!("last-modified: {}\n", last_modified));
+        }
+        std::fs::write(Self::sidecar_path(dump_path), content)
+    }
+
+    pub fn remove(dump_path: &Path) {
+        let _ = std::fs::remove_file(Self::sidecar_path(dump_path));
+    }
+
+    /// Headers saved when `dump_path` was downloaded, `None` when there are none.
+    pub fn load_saved(dump_path: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(Self::sidecar_path(dump_path)).ok()?;
+        let mut headers = Self::default();
+        for line in content.lines() {
+            if let Some(etag) = line.strip_prefix("etag: ") {
+                headers.etag = Some(etag.to_owned());
+            } else if let Some(last_modified) = line.strip_prefix("last-modified: ") {
+                headers.last_modified = Some(last_modified.to_owned());
+            }
+        }
+        Some(headers)
+    }
+
+    /// Headers saved when `dump_path` was downloaded. Dumps cached without
+    /// them fall back to the file modification time.
+    pub fn load(dump_path: &Path) -> Self {
+        if let Some(headers) = Self::load_saved(dump_path) {
+            return headers;
+        }
+        Self {
+            etag: None,
+            last_modified: std::fs::metadata(dump_path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .map(httpdate::fmt_http_date),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use rkyv::{rancor::Error, to_bytes, Archive, Deserialize, Serialize};
+use rustc_hash::FxHashMap;
+use std::{fs::File, io::Write, path::Path};
+use crate::{DumpMeta, WikiPageId};
+
+/// Start of every tables file, followed by the format version.
+const TABLES_MAGIC: [u8; 8] = *b"WIKIMAPS";
+/// Bumped whenever the archived layout of `DumpTables` changes.
+const TABLES_FORMAT_VERSION: u32 = 1;
+const TABLES_HEADER_LEN: usize = 16;
+
+/// What each dump parses to before redirects and links are resolved, so a
+/// later build can reuse the tables of the dumps that did not change.
+#[derive(Archive, Serialize, Deserialize, Debug, Default)]
+pub(crate) struct DumpTables {
+    pub dumps: Vec<DumpMeta>,
+    pub pages: FxHashMap<String, WikiPageId>,
+    // Every article namespace redirect, including the ones leading nowhere.
+    pub redirects: FxHashMap<u32, String>,
+    pub link_targets: FxHashMap<u32, String>,
+    // Link target ids of every page, only keeping the known link targets.
+    pub page_links: FxHashMap<u32, Vec<u32>>,
+}
+
+impl DumpTables {
+    /// Where the tables of the graph at `graph_path` are written, `graph.maps.rkyv` for `graph.rkyv`.
+    pub fn path_for(graph_path: &Path) -> std::path::PathBuf {
+        graph_path.with_extension("maps.rkyv")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let bytes = to_bytes::<Error>(self)?;
+        let mut header = [0u8; TABLES_HEADER_LEN];
+        header[..TABLES_MAGIC.len()].copy_from_slice(&TABLES_MAGIC);
+        header[TABLES_MAGIC.len()..TABLES_MAGIC.len() + 4].copy_from_slice(&TABLES_FORMAT_VERSION.to_le_bytes());
+        // A build reusing these tables may be reading them, so they are
+        // replaced at once rather than rewritten in place.
+        let mut temporary_path = path.as_os_str().to_owned();
+        temporary_path.push(".part");
+        let mut file = File::create(&temporary_path)?;
+        file.write_all(&header)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        std::fs::rename(&temporary_path, path)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = File::open(path).map_err(|err| format!("Failed to open {}: {}", path.display(), err))?;
+        // SAFETY: the file is only read, and `save` replaces it by renaming rather than writing to it.
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        if mmap.len() < TABLES_HEADER_LEN || mmap[..TABLES_MAGIC.len()] != TABLES_MAGIC {
+            return Err(format!("{} is not a dump tables file", path.display()).into());
+        }
+        let version = u32::from_le_bytes(mmap[TABLES_MAGIC.len()..TABLES_MAGIC.len() + 4].try_into().unwrap());
+        if version != TABLES_FORMAT_VERSION {
+            return Err(format!(
+                "{} has tables format version {}, expected {}",
+                path.display(),
+                version,
+                TABLES_FORMAT_VERSION
+            )
+            .into());
+        }
+        Ok(rkyv::from_bytes::<Self, Error>(&mmap[TABLES_HEADER_LEN..])?)
+    }
+}
@@ -0,0 +1,372 @@
+use memchr::{memchr2, memmem};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Keyword after which the tuples of an `INSERT INTO` statement start.
+const VALUES_KEYWORD: &[u8] = b"VALUES";
+/// Start of every line holding an `INSERT INTO` statement.
+const INSERT_PREFIX: &[u8] = b"INSERT INTO ";
+/// Bytes read from the dump at once, the block grows for tuples that do not fit.
+const BLOCK_LEN: usize = 1 << 22;
+
+/// Where the tokenizer is in the dump.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    /// Outside of the values of an `INSERT INTO` statement.
+    Statement,
+    /// Between the tuples of an `INSERT INTO` statement.
+    Values,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FieldKind {
+    Null,
+    /// A number, or anything else written without quotes.
+    Unquoted,
+    Quoted,
+    /// A quoted string holding escapes or doubled quotes.
+    Escaped,
+}
+
+/// Where a field is in the block.
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    start: usize,
+    end: usize,
+    kind: FieldKind,
+}
+
+/// Splits the `INSERT INTO` statements of a MySQL dump into tuples of fields,
+/// whatever quotes, commas and parentheses their strings hold.
+///
+/// The dump is read by large blocks and fields borrow from them, strings
+/// being only unescaped and copied when they hold escapes. Anything outside
+/// of the values of an `INSERT INTO` statement, like the `CREATE TABLE`
+/// header or comments, is skipped.
+pub struct SqlTokenizer<R> {
+    reader: R,
+    block: Vec<u8>,
+    /// Bytes of the block not tokenized yet.
+    pos: usize,
+    end: usize,
+    eof: bool,
+    state: State,
+    spans: Vec<Span>,
+}
+
+impl<R: Read> SqlTokenizer<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, block: vec![0; BLOCK_LEN], pos: 0, end: 0, eof: false, state: State::Statement, spans: Vec::new() }
+    }
+
+    /// Reads more of the dump after the bytes not tokenized yet, `false` at the end of the dump.
+    fn refill(&mut self) -> io::Result<bool> {
+        if self.eof {
+            return Ok(false);
+        }
+        self.block.copy_within(self.pos..self.end, 0);
+        self.end -= self.pos;
+        self.pos = 0;
+        if self.end == self.block.len() {
+            self.block.resize(self.block.len() * 2, 0);
+        }
+        let previous_end = self.end;
+        while self.end < self.block.len() {
+            match self.reader.read(&mut self.block[self.end..]) {
+                Ok(0) => {
+                    self.eof = true;
+                    break;
+                }
+                Ok(read) => self.end += read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(self.end > previous_end)
+    }
+
+    /// Names of the columns in the `CREATE TABLE` statement, which must be
+    /// read before any tuple.
+    pub fn read_columns(&mut self) -> io::Result<Vec<String>> {
+        loop {
+            let unread = &self.block[self.pos..self.end];
+            if let Some(start) = memmem::find(unread, b"CREATE TABLE")
+                && let Some(length) = memmem::find(&unread[start..], b"\n)")
+            {
+                let columns = unread[start..start + length]
+                    .split(|byte| *byte == b'\n')
+                    .filter_map(|line| {
+                        let name = line.trim_ascii_start().strip_prefix(b"`")?;
+                        let name_end = memchr::memchr(b'`', name)?;
+                        Some(String::from_utf8_lossy(&name[..name_end]).into_owned())
+                    })
+                    .collect();
+                self.pos += start + length;
+                return Ok(columns);
+            }
+            if !self.refill()? {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "No complete CREATE TABLE statement in the dump"));
+            }
+        }
+    }
+
+    /// The next tuple, `None` once the dump is over.
+    pub fn next_tuple(&mut self) -> io::Result<Option<Tuple<'_>>> {
+        loop {
+            match self.state {
+                State::Statement => match memmem::find(&self.block[self.pos..self.end], VALUES_KEYWORD) {
+                    Some(index) => {
+                        self.pos += index + VALUES_KEYWORD.len();
+                        self.state = State::Values;
+                    }
+                    None => {
+                        // Keeps what could be the start of `VALUES`.
+                        self.pos = self.end.saturating_sub(VALUES_KEYWORD.len() - 1).max(self.pos);
+                        if !self.refill()? {
+                            return Ok(None);
+                        }
+                    }
+                },
+                State::Values => {
+                    let Some(&byte) = self.block[self.pos..self.end].first() else {
+                        if !self.refill()? {
+                            return Ok(None);
+                        }
+                        continue;
+                    };
+                    match byte {
+                        b'(' => match scan_tuple(&self.block[..self.end], self.pos + 1, &mut self.spans)? {
+                            Some(tuple_end) => {
+                                self.pos = tuple_end;
+                                return Ok(Some(Tuple { block: &self.block, spans: &self.spans }));
+                            }
+                            // Tokenized again from its start once more of the dump is read.
+                            None => {
+                                if !self.refill()? {
+                                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "The dump ended inside a tuple"));
+                                }
+                            }
+                        },
+                        b';' => {
+                            self.pos += 1;
+                            self.state = State::Statement;
+                        }
+                        _ => self.pos += 1,
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Spans of the fields of the tuple starting at `start`, after its opening
+/// parenthesis. Returns where the tuple ends, `None` when `block` ends first.
+fn scan_tuple(block: &[u8], start: usize, spans: &mut Vec<Span>) -> io::Result<Option<usize>> {
+    let skip_whitespace = |mut index: usize| {
+        while block.get(index).is_some_and(u8::is_ascii_whitespace) {
+            index += 1;
+        }
+        index
+    };
+    spans.clear();
+    let mut index = start;
+    loop {
+        index = skip_whitespace(index);
+        let Some(&first) = block.get(index) else {
+            return Ok(None);
+        };
+        if first == b'\'' {
+            let start = index + 1;
+            let mut kind = FieldKind::Quoted;
+            let mut search = start;
+            let end = loop {
+                let Some(offset) = memchr2(b'\'', b'\\', &block[search..]) else {
+                    return Ok(None);
+                };
+                let at = search + offset;
+                match (block[at], block.get(at + 1)) {
+                    (_, None) => return Ok(None),
+                    // An escaped byte, or a quote doubled to stand for one.
+                    (b'\\', _) | (b'\'', Some(b'\'')) => {
+                        kind = FieldKind::Escaped;
+                        search = at + 2;
+                    }
+                    _ => break at,
+                }
+            };
+            spans.push(Span { start, end, kind });
+            index = skip_whitespace(end + 1);
+        } else {
+            let Some(offset) = memchr2(b',', b')', &block[index..]) else {
+                return Ok(None);
+            };
+            let end = index + offset;
+            let value = block[index..end].trim_ascii_end();
+            let kind = if value == b"NULL" { FieldKind::Null } else { FieldKind::Unquoted };
+            spans.push(Span { start: index, end: index + value.len(), kind });
+            index = end;
+        }
+        match block.get(index) {
+            None => return Ok(None),
+            Some(b',') => index += 1,
+            Some(b')') => return Ok(Some(index + 1)),
+            Some(&other) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unexpected {:?} after a string in the dump", other as char),
+                ));
+            }
+        }
+    }
+}
+
+/// Fields of a tuple, borrowed from the block of the dump holding it.
+pub struct Tuple<'a> {
+    block: &'a [u8],
+    spans: &'a [Span],
+}
+
+impl<'a> Tuple<'a> {
+    pub fn get(&self, index: usize) -> Option<Field<'a>> {
+        let span = self.spans.get(index)?;
+        Some(Field { raw: &self.block[span.start..span.end], kind: span.kind })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Field<'a> {
+    raw: &'a [u8],
+    kind: FieldKind,
+}
+
+impl<'a> Field<'a> {
+    /// Bytes of the field, `None` for `NULL`, only copied for strings holding escapes.
+    pub fn bytes(&self) -> Option<Cow<'a, [u8]>> {
+        match self.kind {
+            FieldKind::Null => None,
+            FieldKind::Unquoted | FieldKind::Quoted => Some(Cow::Borrowed(self.raw)),
+            FieldKind::Escaped => Some(Cow::Owned(unescape(self.raw))),
+        }
+    }
+
+    /// The field as text, `None` for `NULL`, invalid UTF-8 being replaced.
+    pub fn to_str(self) -> Option<Cow<'a, str>> {
+        Some(match self.bytes()? {
+            Cow::Borrowed(bytes) => String::from_utf8_lossy(bytes),
+            Cow::Owned(bytes) => Cow::Owned(String::from_utf8(bytes).unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned())),
+        })
+    }
+
+    /// Value of an unquoted unsigned integer, `None` for anything else.
+    pub fn to_u32(self) -> Option<u32> {
+        if self.kind != FieldKind::Unquoted || self.raw.is_empty() {
+            return None;
+        }
+        self.raw.iter().try_fold(0u32, |value, byte| {
+            let digit = byte.wrapping_sub(b'0');
+            if digit > 9 {
+                return None;
+            }
+            value.checked_mul(10)?.checked_add(digit as u32)
+        })
+    }
+}
+
+fn unescape(raw: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(raw.len());
+    let mut iter = raw.iter();
+    while let Some(&byte) = iter.next() {
+        match byte {
+            b'\\' => match iter.next() {
+                Some(b'0') => bytes.push(0),
+                Some(b'b') => bytes.push(0x08),
+                Some(b'n') => bytes.push(b'\n'),
+                Some(b'r') => bytes.push(b'\r'),
+                Some(b't') => bytes.push(b'\t'),
+                Some(b'Z') => bytes.push(0x1a),
+                // Only escaped for LIKE patterns, MySQL keeps their backslash.
+                Some(&escaped @ (b'%' | b'_')) => bytes.extend_from_slice(&[b'\\', escaped]),
+                Some(&escaped) => bytes.push(escaped),
+                None => {}
+            },
+            // The first of two quotes standing for one.
+            b'\'' => {
+                bytes.push(b'\'');
+                iter.next();
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    bytes
+}
+
+/// Index in `columns` of every column of `names`.
+pub fn column_indices<const N: usize>(columns: &[String], names: [&str; N]) -> io::Result<[usize; N]> {
+    let mut indices = [0; N];
+    for (index, name) in indices.iter_mut().zip(names) {
+        *index = columns.iter().position(|column| column == name).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("No {} column in the dump, found {}", name, columns.join(", ")))
+        })?;
+    }
+    Ok(indices)
+}
+
+/// Splits the `INSERT INTO` statements of the dump at `path` into at most
+/// `parts` byte ranges of similar length, each parsable on its own.
+///
+/// Ranges start on the lines of `INSERT INTO` statements, which are true
+/// tuple boundaries as mysqldump escapes the newlines of strings. A dump of
+/// an empty table has no range.
+pub fn find_cut_points(path: &Path, parts: usize) -> io::Result<Vec<(u64, u64)>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let file_size = reader.get_ref().metadata()?.len();
+    let mut line = Vec::new();
+
+    let Some(start_offset) = next_insert_line(&mut reader, 0, &mut line)? else {
+        return Ok(Vec::new());
+    };
+    let chunk_size = (file_size - start_offset) / parts.max(1) as u64;
+
+    let mut cut_points = Vec::new();
+    let mut current_offset = start_offset;
+    for part in 1..=parts {
+        let target = start_offset + chunk_size * part as u64;
+        let end_offset = if part == parts || target >= file_size {
+            file_size
+        } else if target <= current_offset {
+            continue;
+        } else {
+            // Skips the rest of the line `target` falls in, unless it starts one.
+            reader.seek(SeekFrom::Start(target - 1))?;
+            line.clear();
+            let skipped = reader.read_until(b'\n', &mut line)? as u64;
+            next_insert_line(&mut reader, target - 1 + skipped, &mut line)?.unwrap_or(file_size)
+        };
+        if end_offset > current_offset {
+            cut_points.push((current_offset, end_offset));
+            current_offset = end_offset;
+        }
+        if current_offset == file_size {
+            break;
+        }
+    }
+    Ok(cut_points)
+}
+
+/// Offset of the first `INSERT INTO` line from `offset`, the start of the
+/// line `reader` is at.
+fn next_insert_line(reader: &mut BufReader<File>, mut offset: u64, line: &mut Vec<u8>) -> io::Result<Option<u64>> {
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', line)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if line.starts_with(INSERT_PREFIX) {
+            return Ok(Some(offset));
+        }
+        offset += read as u64;
+    }
+}
@@ -0,0 +1,102 @@
+use rkyv::{Archive, Deserialize, Serialize};
+
+/// The links of one direction of the graph, archived exactly like
+/// `Adjacency` in `rust-serverless/src/adjacency.rs`, which reads the graphs
+/// built here: any change to the encoding must be made to both files.
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+pub enum Adjacency {
+    /// Neighbours of node `i` are `edges[offsets[i]..offsets[i + 1]]`.
+    Raw { offsets: Vec<u32>, edges: Vec<u32> },
+    /// Neighbours of node `i` start at byte `offsets[i]`, varint encoded: the
+    /// degree, then each sorted neighbour as its gap minus one from the
+    /// previous neighbour, the first one counting from -1.
+    Compressed { offsets: Vec<u64>, edge_count: u64, bytes: Vec<u8> },
+}
+
+impl Adjacency {
+    pub fn edge_count(&self) -> usize {
+        match self {
+            Adjacency::Raw { edges, .. } => edges.len(),
+            Adjacency::Compressed { edge_count, .. } => *edge_count as usize,
+        }
+    }
+
+    /// The compressed encoding of the same links, which must be sorted and
+    /// deduplicated for every node.
+    pub fn compress(self) -> Adjacency {
+        let Adjacency::Raw { offsets, edges } = self else {
+            return self;
+        };
+
+        let mut compressed_offsets = Vec::with_capacity(offsets.len());
+        let mut bytes = Vec::new();
+        for node_offsets in offsets.windows(2) {
+            let neighbours = &edges[node_offsets[0] as usize..node_offsets[1] as usize];
+            debug_assert!(
+                neighbours.windows(2).all(|pair| pair[0] < pair[1]),
+                "Neighbours must be sorted and deduplicated before compression"
+            );
+            compressed_offsets.push(bytes.len() as u64);
+            write_varint(&mut bytes, neighbours.len() as u32);
+            let mut previous = u32::MAX;
+            for &neighbour in neighbours {
+                write_varint(&mut bytes, neighbour - previous.wrapping_add(1));
+                previous = neighbour;
+            }
+        }
+        compressed_offsets.push(bytes.len() as u64);
+
+        Adjacency::Compressed { offsets: compressed_offsets, edge_count: edges.len() as u64, bytes }
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], position: &mut usize) -> u32 {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[*position];
+        *position += 1;
+        value |= ((byte & 0x7f) as u32) << shift;
+        if byte < 0x80 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+impl ArchivedAdjacency {
+    pub fn node_count(&self) -> u32 {
+        match self {
+            ArchivedAdjacency::Raw { offsets, .. } => (offsets.len() - 1) as u32,
+            ArchivedAdjacency::Compressed { offsets, .. } => (offsets.len() - 1) as u32,
+        }
+    }
+
+    /// Appends the neighbours of `node` to `neighbours`.
+    pub fn extend_neighbours(&self, node: u32, neighbours: &mut Vec<u32>) {
+        match self {
+            ArchivedAdjacency::Raw { offsets, edges } => {
+                let start_offset = offsets[node as usize].to_native() as usize;
+                let end_offset = offsets[(node + 1) as usize].to_native() as usize;
+                neighbours.extend(edges[start_offset..end_offset].iter().map(|edge| edge.to_native()));
+            }
+            ArchivedAdjacency::Compressed { offsets, bytes, .. } => {
+                let mut position = offsets[node as usize].to_native() as usize;
+                let degree = read_varint(bytes, &mut position);
+                let mut previous = u32::MAX;
+                for _ in 0..degree {
+                    previous = previous.wrapping_add(1) + read_varint(bytes, &mut position);
+                    neighbours.push(previous);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+use crate::adjacency::ArchivedAdjacency;
+use crate::{map_graph_file, ArchivedCsrGraph, GRAPH_HEADER_LEN};
+
+/// Prints what a graph file holds and how it was built.
+pub fn inspect(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mmap = map_graph_file(path)?;
+    // SAFETY: validated by `map_graph_file`.
+    let graph = unsafe { rkyv::access_unchecked::<ArchivedCsrGraph>(&mmap[GRAPH_HEADER_LEN..]) };
+    let meta = &graph.meta;
+
+    println!("{}: {} bytes, format version {}", path.display(), mmap.len(), meta.format_version);
+    println!("lang: {}", meta.lang);
+    println!(
+        "built at {} by {} in {:.1} s",
+        httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(meta.built_at.to_native())),
+        meta.builder_version,
+        meta.build_duration_ms.to_native() as f64 / 1000.0
+    );
+    for dump in meta.dumps.iter() {
+        println!(
+            "dump {}: etag {}, last modified {}",
+            dump.file_type,
+            dump.etag.as_ref().map_or("unknown", |etag| etag.as_str()),
+            dump.last_modified.as_ref().map_or("unknown", |last_modified| last_modified.as_str())
+        );
+    }
+    println!("nodes: {}, edges: {}", meta.node_count, meta.edge_count);
+    for (name, adjacency) in [("links", &graph.links), ("reverse_links", &graph.reverse_links)] {
+        match adjacency {
+            ArchivedAdjacency::Raw { edges, .. } => println!("{}: raw, {} edges", name, edges.len()),
+            ArchivedAdjacency::Compressed { edge_count, bytes, .. } => {
+                println!("{}: compressed, {} edges in {} bytes", name, edge_count, bytes.len())
+            }
+        }
+    }
+    println!("node order: {}", if graph.sorted_nodes.is_empty() { "page id" } else { "reordered" });
+    println!(
+        "pages: {}, redirects: {}, searchable titles: {}",
+        graph.pages.len(),
+        graph.redirects.len(),
+        graph.title_index.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,217 @@
+use memmap2::Mmap;
+use rkyv::rend::u32_le;
+use rustc_hash::FxHashMap;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use crate::dump_headers::DumpHeaders;
+use crate::dump_tables::DumpTables;
+use crate::{map_graph_file, ArchivedCsrGraph, WikiPageId, GRAPH_HEADER_LEN};
+
+/// Titles listed in the change report for every kind of page change.
+const REPORT_SAMPLE_LEN: usize = 20;
+
+/// A previously built graph and the dump tables it was built from.
+pub struct PreviousBuild {
+    path: PathBuf,
+    mmap: Mmap,
+    pub tables: DumpTables,
+}
+
+impl PreviousBuild {
+    pub fn open(graph_path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mmap = map_graph_file(graph_path)?;
+        let tables_path = DumpTables::path_for(graph_path);
+        let tables = DumpTables::load(&tables_path)
+            .map_err(|err| format!("{} (build the previous graph with --write-maps)", err))?;
+        Ok(Self { path: graph_path.to_path_buf(), mmap, tables })
+    }
+
+    fn graph(&self) -> &ArchivedCsrGraph {
+        // SAFETY: validated by `map_graph_file`.
+        unsafe { rkyv::access_unchecked::<ArchivedCsrGraph>(&self.mmap[GRAPH_HEADER_LEN..]) }
+    }
+
+    /// Whether `file_type` was built from the dump `headers` identify.
+    pub fn has_dump(&self, file_type: &str, headers: &DumpHeaders) -> bool {
+        self.tables
+            .dumps
+            .iter()
+            .find(|dump| dump.file_type == file_type)
+            .is_some_and(|dump| {
+                let previous = DumpHeaders { etag: dump.etag.clone(), last_modified: dump.last_modified.clone() };
+                previous.same_dump(headers)
+            })
+    }
+
+    fn previous_node(&self, page_id: u32) -> Option<u32> {
+        let graph = self.graph();
+        if graph.sorted_nodes.is_empty() {
+            graph.page_ids.binary_search_by(|id| id.to_native().cmp(&page_id)).ok().map(|index| index as u32)
+        } else {
+            graph
+                .sorted_nodes
+                .binary_search_by(|node| graph.page_ids[node.to_native() as usize].to_native().cmp(&page_id))
+                .ok()
+                .map(|index| graph.sorted_nodes[index].to_native())
+        }
+    }
+
+    /// What changed since the previous graph, from the resolved maps of the new build.
+    pub fn change_report(
+        &self,
+        reused_dumps: &[&str],
+        pages_map: &FxHashMap<String, WikiPageId>,
+        redirects_map: &FxHashMap<u32, String>,
+        pages_links: &FxHashMap<u32, Vec<u32>>,
+    ) -> Value {
+        let graph = self.graph();
+
+        let mut pages_added: Vec<&str> = Vec::new();
+        let mut pages_recreated = 0u64;
+        let mut became_redirect = 0u64;
+        let mut no_longer_redirect = 0u64;
+        for (title, page) in pages_map {
+            match graph.pages.get(title.as_str()) {
+                None => pages_added.push(title),
+                Some(previous) => {
+                    if previous.id.to_native() != page.id {
+                        pages_recreated += 1;
+                    }
+                    if previous.is_redirect != page.is_redirect {
+                        if page.is_redirect {
+                            became_redirect += 1;
+                        } else {
+                            no_longer_redirect += 1;
+                        }
+                    }
+                }
+            }
+        }
+        let mut pages_removed: Vec<&str> = graph
+            .pages
+            .keys()
+            .map(|title| title.as_str())
+            .filter(|title| !pages_map.contains_key(*title))
+            .collect();
+
+        let mut redirects_added = 0u64;
+        let mut redirects_retargeted = 0u64;
+        for (page_id, title) in redirects_map {
+            match graph.redirects.get(&u32_le::from_native(*page_id)) {
+                None => redirects_added += 1,
+                Some(previous_title) if previous_title.as_str() != title => redirects_retargeted += 1,
+                Some(_) => {}
+            }
+        }
+        let redirects_removed = graph
+            .redirects
+            .keys()
+            .filter(|page_id| !redirects_map.contains_key(&page_id.to_native()))
+            .count();
+
+        // Links are compared by page id, node indices differ between builds.
+        let mut links_added = 0u64;
+        let mut links_removed = 0u64;
+        let mut pages_with_changed_links = 0u64;
+        let mut seen = vec![false; graph.page_ids.len()];
+        let mut previous_links = Vec::new();
+        let mut links = Vec::new();
+        for (page_id, targets) in pages_links {
+            links.clear();
+            links.extend_from_slice(targets);
+            links.sort_unstable();
+            links.dedup();
+
+            previous_links.clear();
+            if let Some(node) = self.previous_node(*page_id) {
+                seen[node as usize] = true;
+                graph.links.extend_neighbours(node, &mut previous_links);
+                for neighbour in previous_links.iter_mut() {
+                    *neighbour = graph.page_ids[*neighbour as usize].to_native();
+                }
+                previous_links.sort_unstable();
+                previous_links.dedup();
+            }
+
+            let (added, removed) = sorted_difference(&previous_links, &links);
+            links_added += added;
+            links_removed += removed;
+            if added + removed > 0 {
+                pages_with_changed_links += 1;
+            }
+        }
+        for node in 0..graph.links.node_count() {
+            if seen[node as usize] {
+                continue;
+            }
+            previous_links.clear();
+            graph.links.extend_neighbours(node, &mut previous_links);
+            if !previous_links.is_empty() {
+                links_removed += previous_links.len() as u64;
+                pages_with_changed_links += 1;
+            }
+        }
+
+        let previous_edge_count = graph.meta.edge_count.to_native();
+        let (pages_added_count, pages_removed_count) = (pages_added.len(), pages_removed.len());
+        pages_added.sort_unstable();
+        pages_removed.sort_unstable();
+        pages_added.truncate(REPORT_SAMPLE_LEN);
+        pages_removed.truncate(REPORT_SAMPLE_LEN);
+
+        json!({
+            "previous": {
+                "path": self.path.display().to_string(),
+                "built_at": graph.meta.built_at.to_native(),
+                "node_count": graph.meta.node_count.to_native(),
+                "edge_count": previous_edge_count,
+            },
+            "reused_dumps": reused_dumps,
+            "pages": {
+                "added": pages_added_count,
+                "removed": pages_removed_count,
+                "recreated": pages_recreated,
+                "became_redirect": became_redirect,
+                "no_longer_redirect": no_longer_redirect,
+                "added_sample": pages_added,
+                "removed_sample": pages_removed,
+            },
+            "redirects": {
+                "added": redirects_added,
+                "removed": redirects_removed,
+                "retargeted": redirects_retargeted,
+            },
+            "links": {
+                "added": links_added,
+                "removed": links_removed,
+                "pages_changed": pages_with_changed_links,
+            },
+            "edge_count": previous_edge_count + links_added - links_removed,
+        })
+    }
+}
+
+/// How many values only `current` and only `previous` hold, both sorted and deduplicated.
+fn sorted_difference(previous: &[u32], current: &[u32]) -> (u64, u64) {
+    let (mut added, mut removed) = (0, 0);
+    let (mut i, mut j) = (0, 0);
+    while i < previous.len() && j < current.len() {
+        match previous[i].cmp(&current[j]) {
+            std::cmp::Ordering::Less => {
+                removed += 1;
+                i += 1;
+            }
+            std::cmp::Ordering::Greater => {
+                added += 1;
+                j += 1;
+            }
+            std::cmp::Ordering::Equal => {
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    added += (current.len() - j) as u64;
+    removed += (previous.len() - i) as u64;
+    (added, removed)
+}
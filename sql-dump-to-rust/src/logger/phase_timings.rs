@@ -0,0 +1,58 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+struct PhaseTiming {
+    name: String,
+    start: Duration,
+    end: Duration,
+}
+
+/// When every build phase started and ended since the build started, shared
+/// by the phases running concurrently.
+#[derive(Clone)]
+pub struct PhaseTimings {
+    build_start: Instant,
+    phases: Arc<Mutex<Vec<PhaseTiming>>>,
+}
+
+impl PhaseTimings {
+    pub fn new(build_start: Instant) -> Self {
+        Self { build_start, phases: Arc::new(Mutex::new(Vec::new())) }
+    }
+
+    /// Time since the build started.
+    pub fn elapsed(&self) -> Duration {
+        self.build_start.elapsed()
+    }
+
+    /// Records the phase `name`, started at `start` and ending now.
+    pub fn record(&self, name: impl Into<String>, start: Duration) {
+        let end = self.elapsed();
+        self.phases.lock().expect("Phase timings are poisoned").push(PhaseTiming { name: name.into(), start, end });
+    }
+
+    /// Runs `phase` and records it as `name`.
+    pub async fn time<T>(&self, name: impl Into<String>, phase: impl Future<Output = T>) -> T {
+        let start = self.elapsed();
+        let output = phase.await;
+        self.record(name, start);
+        output
+    }
+
+    /// One line per phase in the order they started, overlapping phases ran concurrently.
+    pub fn report(&self) -> String {
+        let mut phases = self.phases.lock().expect("Phase timings are poisoned");
+        phases.sort_by_key(|phase| phase.start);
+        let mut report = format!("Phase timings, in seconds since the build started ({:.2}s):", self.elapsed().as_secs_f64());
+        for phase in phases.iter() {
+            report.push_str(&format!(
+                "\n  {:<20} {:>9.2} -> {:>9.2} {:>9.2}s",
+                phase.name,
+                phase.start.as_secs_f64(),
+                phase.end.as_secs_f64(),
+                (phase.end - phase.start).as_secs_f64()
+            ));
+        }
+        report
+    }
+}
@@ -1,819 +1,639 @@
-use async_gen::futures_core::Stream;
 use flate2::read::GzDecoder;
-use indicatif::{ProgressBar, ProgressStyle};
-use lazy_static::lazy_static;
-use async_stream::stream;
-use futures::{StreamExt, TryStreamExt};
-use regex::Regex;
-use reqwest::Client;
 use rkyv::{
     rancor::Error, to_bytes, Archive, Deserialize, Serialize
 };
-use utf8_chars::BufReadCharsExt;
-use std::{collections::HashMap, fs::File, io::{BufRead, BufReader, Read, Seek, SeekFrom, Write}, num::NonZero, sync::Arc, time::{Duration, Instant}};
-use tokio::{sync::Mutex, task};
-use rustc_hash::{FxBuildHasher, FxHashMap};
+use std::{collections::{hash_map::Entry, HashMap}, fs::File, hash::Hash, io::{BufReader, Read, Seek, SeekFrom, Write}, sync::Arc, time::Instant};
+use rustc_hash::FxHashMap;
 use crate::dump_logger::DumpProgressLogger;
+use crate::phase_timings::PhaseTimings;
+use crate::dump_headers::DumpHeaders;
+use crate::dump_tables::DumpTables;
+use crate::checkpoint::{Checkpoint, CheckpointInput};
+use crate::dump_checksum::DumpChecksum;
+use crate::sql_tokenizer::{column_indices, find_cut_points, Field, SqlTokenizer};
+use crate::adjacency::Adjacency;
+use crate::reorder::NodeOrder;
+use crate::cli::{Command, Config};
 #[path = "logger/dump_logger.rs"] mod dump_logger;
+#[path = "logger/phase_timings.rs"] mod phase_timings;
+#[path = "dump/dump_headers.rs"] mod dump_headers;
+#[path = "dump/dump_tables.rs"] mod dump_tables;
+#[path = "dump/checkpoint.rs"] mod checkpoint;
+#[path = "dump/dump_checksum.rs"] mod dump_checksum;
+#[path = "dump/dump_download.rs"] mod dump_download;
+#[path = "dump/sql_tokenizer.rs"] mod sql_tokenizer;
+#[path = "graph/adjacency.rs"] mod adjacency;
+#[path = "graph/reorder.rs"] mod reorder;
+#[path = "graph/previous_build.rs"] mod previous_build;
+#[path = "graph/inspect.rs"] mod inspect;
+#[path = "cli/cli.rs"] mod cli;
 
 use dotenv::dotenv;
-use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 
-lazy_static! {
-    static ref WIKI_LANG: String =
-        env::var("WIKI_LANG").expect("WIKI_LANG environment variable not set");
-    
-}
+static CONFIG: OnceLock<Config> = OnceLock::new();
 
-#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
-struct CsrGraph {
-    offsets: Vec<u32>,
-    edges: Vec<u32>,
-    reverse_offsets: Vec<u32>,
-    reverse_edges: Vec<u32>,
-    page_id_to_index: HashMap<u32, u32>,
-    index_to_page_id: HashMap<u32, u32>,
+/// Command line options, set once at startup.
+fn config() -> &'static Config {
+    CONFIG.get().expect("Config is set at startup")
 }
 
-pub struct SqlDumpStream {
-    pub decoder: GzDecoder<File>,
-    pub size: u64,
-    pub file_handle_for_progress: File,
+/// Cache path of a dump, `extension` being `sql.gz`, or `sql` once gunzipped.
+fn dump_path(file_type: &str, extension: &str) -> PathBuf {
+    let config = config();
+    config.cache_dir.join(&config.lang).join(format!("{}wiki-{}-{}.{}", config.lang, config.date, file_type, extension))
 }
 
-async fn sql_dump_stream_from_cache(file_type: &str) -> Result<SqlDumpStream, Box<dyn std::error::Error>> {
-    let path = format!("cache/{}/{}wiki-latest-{}.sql.gz", &*WIKI_LANG, &*WIKI_LANG, file_type);
-    let file_path = std::path::Path::new(&path);
-    if file_path.exists() {
-        println!("Using cached file: {}", path);
-        let saved_file = std::fs::File::open(file_path)?;
-        let progress_handle = saved_file.try_clone()?;
-        let size = saved_file.metadata()?.len();
-        return Ok(SqlDumpStream {
-            decoder: flate2::read::GzDecoder::new(saved_file),
-            size,
-            file_handle_for_progress: progress_handle,
-        });
-    }
-    let url = format!("https://dumps.wikimedia.org/{}wiki/latest/{}wiki-latest-{}.sql.gz",  &*WIKI_LANG, &*WIKI_LANG, file_type);
-    println!("Downloading {}...", url);
-
-    let client = Client::new();
-    let mut res = client.get(&url).send().await?;
-    let total_size = res.content_length().unwrap_or(0);
-    let pb = ProgressBar::new(total_size);
-    pb.set_style(ProgressStyle::default_bar()
-        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-        .unwrap()
-        .progress_chars("#>-"));
-    let file_path = std::path::Path::new(&path);
-
-    // Create parent directory if it doesn't exist
-    if let Some(parent) = file_path.parent() {
-        std::fs::create_dir_all(parent)?;
-    }
-
-    let mut file = std::fs::File::create(file_path)?;
-    
-    let mut downloaded: u64 = 0;
-    let mut last_log_time = Instant::now();
-    let log_interval = Duration::from_secs(10);
-
-    // Note: `res` must be mutable to be read from.
-    while let Some(chunk) = res.chunk().await? {
-        let chunk_len = chunk.len() as u64;
-        file.write_all(&chunk)?;
-        pb.inc(chunk_len);
-        downloaded += chunk_len;
-
-        if last_log_time.elapsed() >= log_interval {
-            println!(
-                "Downloading {}: {}/{} bytes ({:.2}%)",
-                url,
-                downloaded,
-                total_size,
-                (downloaded as f64 / total_size as f64) * 100.0
-            );
-            last_log_time = Instant::now();
-        }
-    }
-    pb.finish_with_message("Downloaded");
-    // Open the newly downloaded file
-    let saved_file = std::fs::File::open(file_path)?;
-    let progress_handle = saved_file.try_clone()?;
-
-    // Return a decompressed stream.
-    Ok(SqlDumpStream {
-        decoder: flate2::read::GzDecoder::new(saved_file),
-        size: total_size,
-        file_handle_for_progress: progress_handle,
-    })
+fn dump_url(file_type: &str) -> String {
+    let config = config();
+    format!("{}/{}wiki/{}/{}wiki-{}-{}.sql.gz", config.mirror, config.lang, config.date, config.lang, config.date, file_type)
 }
 
-async fn sql_dump_download_gunzipped(file_type: &str) -> Result<(), Box<dyn std::error::Error>> {
-    use async_compression::futures::bufread::GzipDecoder;
-    use futures::{
-        io::{self, BufReader, ErrorKind},
-        prelude::*,
-    };
-    use tokio_util::compat::TokioAsyncWriteCompatExt;
-
-    let path = format!("cache/{}/{}wiki-latest-{}.sql", &*WIKI_LANG, &*WIKI_LANG, file_type);
-    let file_path = std::path::Path::new(&path);
-    if file_path.exists() {
-        println!("Using cached file: {}", path);
-        return Ok(());
+/// Dump given as a local file, explicitly or through a `file://` mirror.
+fn local_dump_path(file_type: &str) -> Option<PathBuf> {
+    if let Some(path) = config().local_dump(file_type) {
+        return Some(path.to_path_buf());
     }
-    let url = format!("https://dumps.wikimedia.org/{}wiki/latest/{}wiki-latest-{}.sql.gz",  &*WIKI_LANG, &*WIKI_LANG, file_type);
-    println!("Downloading {}...", url);
+    dump_url(file_type).strip_prefix("file://").map(PathBuf::from)
+}
 
-    let client = Client::new();
-    let res = client.get(&url).send().await?;
-    let total_size = res.content_length().unwrap_or(0);
-    let pb = Arc::new(ProgressBar::new(total_size));
-    pb.set_style(ProgressStyle::default_bar()
-        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-        .unwrap()
-        .progress_chars("#>-"));
-    let file_path = std::path::Path::new(&path);
+fn is_gzip(path: &Path) -> bool {
+    let mut magic = [0u8; 2];
+    File::open(path).and_then(|mut file| file.read_exact(&mut magic)).is_ok() && magic == [0x1f, 0x8b]
+}
 
-    // Create parent directory if it doesn't exist
-    if let Some(parent) = file_path.parent() {
-        std::fs::create_dir_all(parent)?;
+/// File a dump is parsed from, its local file or its download in the cache.
+/// `gunzipped` asks for a plain file, gunzipped into the cache when needed.
+fn dump_file_path(file_type: &str, gunzipped: bool) -> PathBuf {
+    match local_dump_path(file_type) {
+        Some(path) if !gunzipped || !is_gzip(&path) => path,
+        _ => dump_path(file_type, if gunzipped { "sql" } else { "sql.gz" }),
     }
-
-    let file = tokio::fs::File::create(file_path).await?;
-    let mut compat_file = file.compat_write();
-
-    let mut downloaded_size: u64 = 0;
-    let mut last_log_time = Instant::now();
-    let log_interval = Duration::from_secs(10);
-    let url_clone = url.clone();
-    let pb_clone = Arc::clone(&pb);
-
-    let reader = res
-        .bytes_stream()
-        .map(move |chunk| {
-            match chunk {
-                Ok(bytes) => {
-                    let bytes_len = bytes.len() as u64;
-                    downloaded_size += bytes_len;
-                    pb_clone.set_position(downloaded_size); // Update progress bar
-                    if last_log_time.elapsed() >= log_interval {
-                        println!(
-                            "Downloading {}: {}/{} bytes ({:.2}%)",
-                            url_clone.clone(),
-                            downloaded_size,
-                            total_size,
-                            (downloaded_size as f64 / total_size as f64) * 100.0
-                        );
-                        last_log_time = Instant::now();
-                    }
-                    Ok(bytes)
-                }
-                Err(e) => Err(io::Error::new(ErrorKind::Other, e)),
-            }
-        })
-        .map_err(|e| io::Error::new(ErrorKind::Other, e))
-        .into_async_read();
-    let mut decoder = GzipDecoder::new(BufReader::new(reader));
-
-    futures::io::copy(&mut decoder, &mut compat_file).await?;
-
-    pb.finish_with_message("Downloaded");
-    Ok(())
 }
 
+/// Headers of a local or cached dump, local dumps being identified by their modification time.
+fn saved_dump_headers(file_type: &str, gunzipped: bool) -> DumpHeaders {
+    DumpHeaders::load(&local_dump_path(file_type).unwrap_or_else(|| dump_file_path(file_type, gunzipped)))
+}
 
+fn offline_error(file_type: &str, path: &Path) -> Box<dyn std::error::Error> {
+    format!(
+        "The {} dump is not cached at {} and downloads are disabled by --offline, give it with --{}-dump",
+        file_type,
+        path.display(),
+        file_type
+    )
+    .into()
+}
 
+/// Downloads of a dump that does not match its published checksum before giving up.
+const DOWNLOAD_ATTEMPTS: usize = 3;
 
-// Function to find the cut points in the file
-fn find_cut_points(file_path: &str, num_threads: usize) -> Vec<(u64, u64)> {
-    let file = File::open(file_path).expect("Failed to open file");
-    let mut reader = BufReader::new(file);
-
-    // Find the first occurrence of "INSERT INTO `pagelinks` VALUES"
-    let mut start_offset = 0;
-    let mut buffer = [0; 1024];
-    let mut search_str = b"INSERT INTO `pagelinks` VALUES";
-    let mut search_index = 0;
-
-    while let Ok(bytes_read) = reader.read(&mut buffer) {
-        if bytes_read == 0 {
-            break; // End of file
-        }
-
-        for i in 0..bytes_read {
-            if buffer[i] == search_str[search_index] {
-                search_index += 1;
-                if search_index == search_str.len() {
-                    start_offset = reader.seek(SeekFrom::Current(i as i64 - bytes_read as i64 + 1))
-                        .expect("Failed to calculate start_offset");
-                    break;
-                }
-            } else {
-                search_index = 0;
-            }
-        }
+/// A cached dump, either usable as is or to be downloaded and checked against
+/// its published checksum, `None` when it may be used unverified.
+enum CachedDump {
+    Usable,
+    Missing(Option<DumpChecksum>),
+}
 
-        if start_offset > 0 {
-            break;
+/// Published checksum of a dump, `None` when there is none but `--allow-unverified` is given.
+async fn published_checksum(file_type: &str) -> Result<Option<DumpChecksum>, Box<dyn std::error::Error>> {
+    let config = config();
+    let checksum = if config.offline {
+        None
+    } else {
+        let base_url = format!("{}/{}wiki/{}", config.mirror, config.lang, config.date);
+        DumpChecksum::fetch(&base_url, &format!("{}wiki-{}", config.lang, config.date), file_type).await
+    };
+    match checksum {
+        Some(checksum) => Ok(Some(checksum)),
+        None if config.allow_unverified => {
+            println!("No published checksum for the {} dump, using it unverified", file_type);
+            Ok(None)
         }
+        None => Err(format!(
+            "No published checksum to verify the {} dump against, use it anyway with --allow-unverified",
+            file_type
+        )
+        .into()),
     }
+}
 
-    if start_offset == 0 {
-        panic!("Failed to find 'INSERT INTO `pagelinks` VALUES' in file");
+/// Checks a dump cached at `path` the first time it is used, removing it when
+/// it does not match its published checksum.
+async fn check_cached_dump(file_type: &str, path: &Path) -> Result<CachedDump, Box<dyn std::error::Error>> {
+    let exists = path.exists();
+    if exists && DumpChecksum::load_verified(path).is_some() {
+        return Ok(CachedDump::Usable);
     }
-
-    // Calculate the file size
-    let file_size = reader.seek(SeekFrom::End(0)).expect("Failed to seek to end of file");
-
-    // Calculate chunk size based on the adjusted start_offset
-    let adjusted_file_size = file_size - start_offset;
-    let chunk_size = adjusted_file_size / num_threads as u64;
-
-    let mut cut_points = Vec::new();
-    let mut current_offset = start_offset;
-
-    for _ in 0..num_threads {
-        let mut end_offset = current_offset + chunk_size;
-        if end_offset >= file_size {
-            end_offset = file_size;
-        } else {
-            // Adjust end_offset to land on ",(" using a sliding window
-            reader.seek(SeekFrom::Start(end_offset)).expect("Failed to seek to end_offset");
-            let mut buffer = [0; 2];
-            while end_offset < file_size {
-                reader.read_exact(&mut buffer).expect("Failed to read file");
-                if &buffer == b",(" {
-                    break;
+    if !exists && config().offline {
+        return Err(offline_error(file_type, path));
+    }
+    let expected = published_checksum(file_type).await?;
+    if exists {
+        match &expected {
+            None => return Ok(CachedDump::Usable),
+            Some(expected) => {
+                println!("Verifying {}", path.display());
+                if tokio::task::block_in_place(|| expected.of_file(path))? == *expected {
+                    expected.save_verified(path)?;
+                    return Ok(CachedDump::Usable);
                 }
-                end_offset += 1;
-                reader.seek(SeekFrom::Start(end_offset)).expect("Failed to seek to next byte");
+                println!("{} does not match its published {} checksum, downloading it again", path.display(), expected.algorithm.name());
             }
         }
+        std::fs::remove_file(path)?;
+        DumpChecksum::remove_verified(path);
+    }
+    Ok(CachedDump::Missing(expected))
+}
 
-        cut_points.push((current_offset, end_offset));
-        current_offset = end_offset;
+/// Whether a dump downloaded to `path` matches `expected`, recording it as
+/// verified when it does and removing it when it does not.
+fn accept_download(path: &Path, expected: Option<&DumpChecksum>) -> std::io::Result<bool> {
+    let Some(expected) = expected else {
+        return Ok(true);
+    };
+    println!("Verifying {}", path.display());
+    if expected.of_file(path)? == *expected {
+        expected.save_verified(path)?;
+        return Ok(true);
     }
-    println!("Cut points: {:?}", cut_points);
-    cut_points
+    println!("{} does not match its published {} checksum", path.display(), expected.algorithm.name());
+    std::fs::remove_file(path)?;
+    DumpChecksum::remove_verified(path);
+    Ok(false)
 }
 
-async fn multithreaded_pagelink_dump_parser(file:File, start_offset:u64, end_offset:u64) -> impl Stream<Item=Vec<String>>  {stream! {
-    let mut reader = BufReader::new(file);
-    reader.seek(SeekFrom::Start(start_offset)).expect("Failed to seek to start_offset");
+fn download_mismatch_error(file_type: &str) -> Box<dyn std::error::Error> {
+    format!("The {} dump did not match its published checksum after {} downloads", file_type, DOWNLOAD_ATTEMPTS).into()
+}
 
-    let mut ctx = ProcessContext::new();
+/// Bumped whenever the archived layout of `CsrGraph` changes.
+const GRAPH_FORMAT_VERSION: u32 = 4;
+/// Start of every graph file, followed by the format version.
+const GRAPH_MAGIC: [u8; 8] = *b"WIKIGRPH";
+/// Bytes before the archive: magic, format version, then padding keeping the archive 16-byte aligned.
+const GRAPH_HEADER_LEN: usize = 16;
+/// Parse phases in the order they run, named after the dump they parse.
+const PHASES: [&str; 4] = ["page", "redirect", "linktarget", "pagelinks"];
+const BUILDER_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "+", env!("BUILDER_GIT_VERSION"));
 
-    let mut current_offset = start_offset;
-    while current_offset < end_offset {
-        let mut buffer = [0; 8192];
-        let bytes_to_read = std::cmp::min(buffer.len() as u64, end_offset - current_offset) as usize;
-        let bytes_read = reader.read(&mut buffer[..bytes_to_read]).expect("Failed to read file");
-        if bytes_read == 0 {
-            break;
-        }
-        current_offset += bytes_read as u64;
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+struct GraphMeta {
+    format_version: u32,
+    lang: String,
+    dumps: Vec<DumpMeta>,
+    // sql-dump-to-rust version and git revision that built the graph.
+    builder_version: String,
+    // Unix timestamp in seconds.
+    built_at: u64,
+    build_duration_ms: u64,
+    node_count: u32,
+    edge_count: u64,
+}
 
-        for c in buffer[..bytes_read].iter().map(|&b| b as char) {
-            let block_is_finished = process_char(c, &mut ctx);
-            if block_is_finished {
-                yield ctx.values;
-                ctx.values = Vec::new();
-            }
-        }
-    }
+/// Which dump file a graph was built from.
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+struct DumpMeta {
+    file_type: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
 
-    pub struct ProcessContext {
-        pub inside_parenthesis: bool,
-        pub inside_string: bool,
-        pub escaped: bool,
-        pub current_value: String,
-        pub values: Vec<String>,
-    }
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq)]
+struct CsrGraph {
+    meta: GraphMeta,
+    // Outgoing links of every node, and incoming links for the backward search.
+    links: Adjacency,
+    reverse_links: Adjacency,
+    // Page id of every node.
+    page_ids: Vec<u32>,
+    // Nodes sorted by page id when they were reordered for locality, empty
+    // when `page_ids` is itself sorted.
+    sorted_nodes: Vec<u32>,
+    // Title lookup tables so the server can resolve titles (and redirects) to page ids.
+    pages: HashMap<String, WikiPageId>,
+    redirects: HashMap<u32, String>,
+    // Page title of every node, indexed like offsets.
+    titles: Vec<String>,
+    // Index of every non redirect node, sorted by title for prefix search.
+    title_index: Vec<u32>,
+}
 
-    impl ProcessContext {
-        /// Creates a new, empty ProcessContext.
-        pub fn new() -> Self {
-            Self {
-                inside_parenthesis: false,
-                inside_string: false,
-                escaped: false,
-                current_value: String::new(),
-                values: Vec::new(),
-            }
-        }
+/// Maps the graph file at `path` after checking its header and archive.
+fn map_graph_file(path: &Path) -> Result<memmap2::Mmap, Box<dyn std::error::Error>> {
+    let file = File::open(path).map_err(|err| format!("Failed to open {}: {}", path.display(), err))?;
+    // SAFETY: the file is only read, it must not be rewritten while mapped.
+    let mmap = unsafe { memmap2::Mmap::map(&file)? };
+    if mmap.len() < GRAPH_HEADER_LEN || mmap[..GRAPH_MAGIC.len()] != GRAPH_MAGIC {
+        return Err(format!("{} is not a graph file", path.display()).into());
     }
-
-    fn process_char(c: char, ctx: &mut ProcessContext) -> bool {
-        if ctx.inside_parenthesis {
-            if c == ',' {
-                ctx.values.push(std::mem::take(&mut ctx.current_value));
-            } else if c == ')' {
-                ctx.inside_parenthesis = false;
-                ctx.values.push(std::mem::take(&mut ctx.current_value));
-                return true;
-            } else {
-                ctx.current_value.push(c);
-            }
-        } else if c == '(' {
-            ctx.inside_parenthesis = true;
-        }
-        return false;
+    let version = u32::from_le_bytes(mmap[GRAPH_MAGIC.len()..GRAPH_MAGIC.len() + 4].try_into().unwrap());
+    if version != GRAPH_FORMAT_VERSION {
+        return Err(format!(
+            "{} has graph format version {}, expected {}, migrate it with migrate-graph first",
+            path.display(),
+            version,
+            GRAPH_FORMAT_VERSION
+        )
+        .into());
     }
-}}
-
-struct MultithreadSharedReadContext {
-    pub pages_map: &'static HashMap<String, WikiPageId, FxBuildHasher>,
-    pub redirects_map: &'static HashMap<u32, String, FxBuildHasher>,
-    pub linktarget_map: &'static HashMap<u32, String, FxBuildHasher>,
+    rkyv::access::<ArchivedCsrGraph, Error>(&mmap[GRAPH_HEADER_LEN..])?;
+    Ok(mmap)
 }
 
-struct MultithreadWriteContext {
-    pub pages_links: HashMap<u32, Vec<u32>, FxBuildHasher>,
-    pub links_count: u64,
+pub struct SqlDumpStream {
+    // Gunzips the dump unless it is a plain SQL file.
+    pub decoder: Box<dyn Read + Send>,
+    pub size: u64,
+    pub file_handle_for_progress: File,
 }
 
-async fn launch_multithread_pagelinks_parser(ctx: Arc<DumpParserContext>) -> (&'static mut u64, &'static mut HashMap<u32, Vec<u32>, FxBuildHasher>) {
-    let file_type = "pagelinks";
-    sql_dump_download_gunzipped(file_type).await.expect("Failed to download pagelinks dump");
-    let file_path = format!("cache/{}/{}wiki-latest-{}.sql", &*WIKI_LANG, &*WIKI_LANG, file_type);
-    let num_threads = num_threads::num_threads().unwrap_or(unsafe { NonZero::new_unchecked(1) }).get(); // Get the number of available threads
-
-    let cut_points = find_cut_points(&file_path, num_threads);
-
-    // let mctx = MultithreadSharedReadContext {
-    //     pages_map: &ctx.pages_map,
-    //     redirects_map: &ctx.redirects_map,
-    //     linktarget_map: &ctx.linktarget_map,
-    // };
-
-    // std::sync::Arc::from_raw(ptr)
-
-    let write_context_with_handles:Vec<(Arc<Mutex<MultithreadWriteContext>>, tokio::task::JoinHandle<()>)> = 
-        cut_points.into_iter().enumerate()
-        .map(|(i,(start_offset, end_offset))| {
-            let read_ctx = Arc::clone(&ctx);
-            let write_ctx = Arc::new(Mutex::new(MultithreadWriteContext {
-                pages_links: FxHashMap::with_hasher(FxBuildHasher::default()),
-                links_count: 0,
-            }));
-            let file_path_clone = file_path.clone();
-            let thread_name = format!("Thread-{}", i + 1);
-
-            let write_ctx_clone = Arc::clone(&write_ctx);
-
-            let handle: tokio::task::JoinHandle<()> = tokio::spawn(async move {
-                multithread_parse_and_load_page_links(&read_ctx,  &write_ctx_clone, file_path_clone, start_offset, end_offset, thread_name).await;
-            });
-            (write_ctx, handle)
+impl SqlDumpStream {
+    fn open(path: &Path) -> std::io::Result<Self> {
+        let file = File::open(path)?;
+        let progress_handle = file.try_clone()?;
+        let size = file.metadata()?.len();
+        let decoder: Box<dyn Read + Send> = if is_gzip(path) { Box::new(GzDecoder::new(file)) } else { Box::new(file) };
+        Ok(SqlDumpStream {
+            decoder,
+            size,
+            file_handle_for_progress: progress_handle,
         })
-        .collect();
-
-    let mut all_pages_links: &'static mut FxHashMap<u32, Vec<u32>> = Box::leak(Box::new(FxHashMap::with_hasher(FxBuildHasher::default())));
-    let mut all_links_count: u64 = 0;
-    
-    for (write_ctx, handle) in write_context_with_handles {
-        handle.await.expect("Thread panicked");
-        let mut write = write_ctx.try_lock().expect("Write context is locked");
-        all_links_count += write.links_count;
-        for (key, value) in write.pages_links.drain() {
-            all_pages_links.entry(key).or_insert_with(Vec::new).extend(value);
-        }
-
     }
-    let all_links_count_static: &'static mut u64 = Box::leak(Box::new(all_links_count));
-
-    (all_links_count_static, all_pages_links)
 }
 
-
-async fn multithread_parse_and_load_page_links(read_ctx:&Arc<DumpParserContext>,  write_ctx:&Arc<Mutex<MultithreadWriteContext>>, file_path:String, start_offset:u64, end_offset:u64, thread_name:String) {
-    let file_type = "pagelinks";
-    let pages_map = &read_ctx.pages_map;
-    let linktarget_map = &read_ctx.linktarget_map;
-    let redirects_map= &read_ctx.redirects_map;
-
-    let mut write = write_ctx.try_lock().expect("Write context is locked");
-
-    let file = File::open(file_path).expect("Failed to open file");
-    let mut progress_handle = file.try_clone().expect("Can't clone file handle to log progress");
-    let stream = multithreaded_pagelink_dump_parser(file, start_offset, end_offset).await;
-
-    let size_of_part = end_offset-start_offset;
-
-    let mut logger = DumpProgressLogger::new(size_of_part, format!("{} pagelinks", thread_name).to_string());
-    let mut count:u64 = 0;
-    tokio::pin!(stream);
-    while let Some(pagelinks_data) = stream.next().await {
-        let mut iter = pagelinks_data.into_iter();
-        let pl_from             = iter.next().expect("pl_from is missing");
-        let pl_from_namespace   = iter.next().expect("pl_from_namespace is missing");
-        if pl_from_namespace != "0" {continue;}
-        let raw_pl_target_id    = iter.next().expect("pl_target_id is missing");
-        let pl_target_id :u32 = raw_pl_target_id.parse().expect("pl_target_id is not a valid u32");
-        let _to_title_option = linktarget_map.get(&pl_target_id);
-        if _to_title_option.is_none() {continue;}
-        let _to_title = _to_title_option.unwrap();
-        let _to_is_redirect_option = pages_map.get(_to_title);
-        if _to_is_redirect_option.is_none() {continue;}
-        let _to_is_redirect = _to_is_redirect_option.unwrap();
-        let _from:u32 = pl_from.parse().expect("pl_from is not a valid u32");
-        let _to = _to_is_redirect.id;
-        let is_redirect = _to_is_redirect.is_redirect;
-        write.links_count += 1;
-        if is_redirect {
-            let _to_resolved_option = resolve_redirect(redirects_map.get(&_to), pages_map, redirects_map);
-            if _to_resolved_option.is_none() {continue;}
-            let _to_resolved =_to_resolved_option.unwrap();
-            if !write.pages_links.contains_key(&_from) {
-                write.pages_links.insert(_from, vec![_to_resolved]);
-            } else {
-                write.pages_links.entry(_from).or_default().push(_to_resolved); 
-            }
-            // nextBatch.push({_from, _to: _to_resolved});
-            // The link resolve to a valid page
-        } else {
-            if !write.pages_links.contains_key(&_from) {
-                write.pages_links.insert(_from, vec![_to]);
-            } else {
-                write.pages_links.entry(_from).or_default().push(_to); 
-            }
-        }
-        count+=1;
-        if (count % 65_536) == 0 {
-            let bytes_read_amount = progress_handle.stream_position().unwrap_or(0) - start_offset;
-            logger.log(bytes_read_amount, count);
-        }
-
+async fn sql_dump_stream_from_cache(file_type: &str) -> Result<SqlDumpStream, Box<dyn std::error::Error>> {
+    if let Some(path) = local_dump_path(file_type) {
+        println!("Using local file: {}", path.display());
+        return SqlDumpStream::open(&path)
+            .map_err(|err| format!("Failed to open the {} dump {}: {}", file_type, path.display(), err).into());
     }
-
-    let bytes_read_amount = progress_handle.stream_position().unwrap_or(0) - start_offset;
-    logger.log(bytes_read_amount, count);
-
-    drop(write);
-    
+    let path = sql_dump_download(file_type).await?;
+    Ok(SqlDumpStream::open(&path)?)
 }
 
-
-
-
-
-async fn sql_dump_parser(reader: &mut BufReader<GzDecoder<File>>, key_to_yield:Vec<&str>) -> impl Stream<Item=Vec<String>>  {stream! {
-    
-    let mut fields: Vec<String> = Vec::new();
-    let mut line_buf = String::new();
-    loop {
-        line_buf.clear();
-        if reader.read_line(&mut line_buf).expect("File incomplete") == 0 {
-            panic!("Did not find CREATE TABLE statement");
+/// Downloads the gzipped dump into the cache unless it is already there,
+/// verified against its published checksum.
+async fn sql_dump_download(file_type: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let path = dump_path(file_type, "sql.gz");
+    let file_path = path.as_path();
+    let expected = match check_cached_dump(file_type, file_path).await? {
+        CachedDump::Usable => {
+            println!("Using cached file: {}", path.display());
+            return Ok(path);
         }
-        if line_buf.starts_with("CREATE TABLE") {
-            break;
+        CachedDump::Missing(expected) => expected,
+    };
+    let url = dump_url(file_type);
+    for _ in 0..DOWNLOAD_ATTEMPTS {
+        println!("Downloading {}...", url);
+        let headers = dump_download::download_resumable(&url, file_path, config().download_retries).await?;
+        if tokio::task::block_in_place(|| accept_download(file_path, expected.as_ref()))? {
+            headers.save(file_path)?;
+            return Ok(path);
         }
     }
+    Err(download_mismatch_error(file_type))
+}
 
-    let re = Regex::new(r"^\s*`(.*)`").unwrap();
-    loop {
-        line_buf.clear();
-        if reader.read_line(&mut line_buf).expect("File incomplete") == 0 {
-            panic!("File ended during CREATE TABLE statement");
-        }
-        let line = &line_buf;
-        let create_table_block_complete = line.starts_with(")");
-        if create_table_block_complete {break;}
-        let field = re.captures(&line).and_then(|caps| {
-            caps.get(1).map(|m| m.as_str())
-        });
-        if field.is_some() {fields.push(field.unwrap().to_owned());}
+/// Downloads the dump into the cache unless it is local or already there,
+/// gunzipping it when `gunzipped`.
+async fn download_dump(file_type: &str, gunzipped: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if gunzipped {
+        sql_dump_download_gunzipped(file_type).await
+    } else {
+        sql_dump_stream_from_cache(file_type).await.map(|_| ())
     }
+}
 
-    let key_to_index: FxHashMap<&str, usize> = fields
-        .iter()
-        .enumerate()
-        .map(|(index, field)| (field.as_str(), index))
-        .collect();
-
-    let index_to_yield: Vec<usize> = key_to_yield
-        .iter()
-        .filter_map(|k| key_to_index.get(k).copied())
-        .collect();
-
-    pub struct ProcessContext {
-        pub inside_parenthesis: bool,
-        pub inside_string: bool,
-        pub escaped: bool,
-        pub current_value: String,
-        pub values: Vec<String>,
-    }
-
-    impl ProcessContext {
-        /// Creates a new, empty ProcessContext.
-        pub fn new() -> Self {
-            Self {
-                inside_parenthesis: false,
-                inside_string: false,
-                escaped: false,
-                current_value: String::new(),
-                values: Vec::new(),
-            }
-        }
+/// Headers of the dump as saved, or as currently published when it is not downloaded yet.
+async fn current_dump_headers(file_type: &str, gunzipped: bool) -> DumpHeaders {
+    if local_dump_path(file_type).is_some() || dump_file_path(file_type, gunzipped).exists() || config().offline {
+        return saved_dump_headers(file_type, gunzipped);
     }
+    let url = dump_url(file_type);
+    DumpHeaders::fetch(&url).await.unwrap_or_else(|err| {
+        println!("Failed to fetch the headers of {}: {}", url, err);
+        DumpHeaders::default()
+    })
+}
 
-    let mut ctx = ProcessContext::new();
-
-    fn process_char(c: char, ctx: &mut ProcessContext) -> bool {
-        if ctx.inside_parenthesis {
-            if ctx.inside_string {
-                if ctx.escaped {
-                    ctx.current_value.push(c);
-                    ctx.escaped = false;
-                } else if c == '\\' {
-                    ctx.escaped = true;
-                } else if c == '\'' {
-                    ctx.inside_string = false;
-                } else {
-                    ctx.current_value.push(c);
-                }
-            } else {
-                if c == '\'' {
-                    ctx.inside_string = true;
-                } else if c == ',' {
-                    ctx.values.push(std::mem::take(&mut ctx.current_value));
-                } else if c == ')' {
-                    ctx.inside_parenthesis = false;
-                    ctx.values.push(std::mem::take(&mut ctx.current_value));
-                    return true;
-                } else {
-                    ctx.current_value.push(c);
-                }
-            }
-        } else if c == '(' {
-            ctx.inside_parenthesis = true;
+async fn sql_dump_download_gunzipped(file_type: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let path = dump_file_path(file_type, true);
+    let file_path = path.as_path();
+    // The parser threads seek in the dump, so a gzipped dump is gunzipped into the cache.
+    let gzipped_path = if let Some(local_path) = local_dump_path(file_type) {
+        // A plain local dump, or a gzipped one gunzipped into the cache before.
+        if file_path.exists() {
+            println!("Using local file: {}", path.display());
+            return Ok(());
         }
-        return false;
-    }
-    for c in reader.chars() {
-        let block_is_finished = process_char(c.expect("Error while reading dump file"), &mut ctx);
-        if block_is_finished {
-            yield index_to_yield
-                .iter()
-                .map(|index| ctx.values.get(*index).expect("File parsing error not enough value inside parsed block").clone())
-                .collect();
-            ctx.values = Vec::new();
+        local_path
+    } else {
+        // A gunzipped dump is verified as long as the gzipped one was.
+        if file_path.exists() && (DumpChecksum::load_verified(file_path).is_some() || config().allow_unverified) {
+            println!("Using cached file: {}", path.display());
+            return Ok(());
         }
+        sql_dump_download(file_type).await?
+    };
+
+    println!("Gunzipping {}...", gzipped_path.display());
+    let gzipped_file = File::open(&gzipped_path)
+        .map_err(|err| format!("Failed to open the {} dump {}: {}", file_type, gzipped_path.display(), err))?;
+    if let Some(parent) = file_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut partial_path = path.as_os_str().to_owned();
+    partial_path.push(".part");
+    let mut partial_file = File::create(&partial_path)?;
+    tokio::task::block_in_place(|| {
+        std::io::copy(&mut GzDecoder::new(BufReader::with_capacity(1 << 20, gzipped_file)), &mut partial_file)?;
+        partial_file.sync_all()
+    })?;
+    std::fs::rename(&partial_path, file_path)?;
+    if let Some(checksum) = DumpChecksum::load_verified(&gzipped_path) {
+        checksum.save_verified(file_path)?;
+    }
+    if local_dump_path(file_type).is_none() {
+        DumpHeaders::load(&gzipped_path).save(file_path)?;
+        // Only the gunzipped dump is read from now on, so the cache does not
+        // keep both. The headers and checksum saved next to the gzipped dump
+        // stay, and are replaced if it is ever downloaded again.
+        std::fs::remove_file(&gzipped_path)?;
     }
-}}
+    Ok(())
+}
 
 pub struct DumpParserContext {
     pub pages_map: &'static mut FxHashMap<String, WikiPageId>,
     pub redirects_map: &'static mut FxHashMap<u32, String>,
     pub linktarget_map: &'static mut FxHashMap<u32, String>,
-    pub pages_links: &'static mut FxHashMap<u32, Vec<u32>>,
-    pub links_count: &'static mut u64,
 }
 
+#[derive(Archive, Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
 pub struct WikiPageId {
     pub id: u32,
     pub is_redirect: bool,
 }
 
 
-async fn parse_and_load_page(ctx: &mut DumpParserContext) {
-    let file_type = "page";
-    let pages_map = &mut ctx.pages_map;
-    let dump_stream: SqlDumpStream = sql_dump_stream_from_cache(file_type).await
-        .expect(&format!("Failed to load wiki {} dump file", file_type));
-    let mut reader = BufReader::new(dump_stream.decoder);
-    let mut progress_handle = dump_stream.file_handle_for_progress;
-
-    let mut logger = DumpProgressLogger::new(dump_stream.size, "Pages".to_string());
-    let mut count:u64 = 0;
-
-    let stream = sql_dump_parser(&mut reader, vec!["page_id","page_title", "page_namespace","page_is_redirect"]).await;
-    tokio::pin!(stream);
-    while let Some(page_data) = stream.next().await {
-        let mut iter = page_data.into_iter();
-        let page_id         = iter.next().expect("page_id is missing");
-        let page_title      = iter.next().expect("page_title is missing");
-        let page_namespace  = iter.next().expect("page_namespace is missing");
-        if page_namespace != "0" {continue;}
-        let page_is_redirect = iter.next().expect("page_is_redirect is missing");
-        let wiki_page_id = WikiPageId {
-            id: page_id.parse().expect("page_id is not a valid u32"),
-            is_redirect: page_is_redirect == "1",
-        };
-        pages_map.insert(page_title, wiki_page_id);
-        count += 1;
-        if (count % 65_536) == 0 {
-            let bytes_read_amount = progress_handle.stream_position().unwrap_or(0);
-            logger.log(bytes_read_amount, count);
-        }
-    }
-
-
-    let bytes_read_amount = progress_handle.stream_position().unwrap_or(0);
-    logger.log(bytes_read_amount, count);
-        
-
+/// Where a parser thread reads its part of a dump from, and how far it got.
+struct DumpPart {
+    file_type: &'static str,
+    tokenizer: SqlTokenizer<Box<dyn Read + Send>>,
+    progress_handle: File,
+    start_offset: u64,
+    size: u64,
+    title: String,
 }
 
-pub struct WikiRedirectToResolve {
-    pub _from_id: u32,
-    pub _to_title: String,
+/// Parses the `file_type` dump into one table per part, in dump order. Every
+/// part is parsed by its own thread, `parse_tuple` adding the fields of
+/// `column_names` to the table and returning whether the tuple was kept.
+///
+/// With `--multithread` the dump is gunzipped into the cache and cut on
+/// `INSERT INTO` lines into a part per thread, all sharing the schema of its
+/// `CREATE TABLE` header. Otherwise the gzipped dump is parsed as one part.
+async fn parse_dump<T, F, const N: usize>(file_type: &'static str, label: &str, column_names: [&'static str; N], parse_tuple: F) -> Vec<T>
+where
+    T: Default + Send + 'static,
+    F: Fn(&mut T, [Field<'_>; N]) -> bool + Send + Sync + 'static,
+{
+    let (dump_columns, parts) = if config().multithread {
+        sql_dump_download_gunzipped(file_type).await
+            .unwrap_or_else(|err| panic!("Failed to load wiki {} dump file: {}", file_type, err));
+        let file_path = dump_file_path(file_type, true);
+        // Only the start of the dump holds the columns, shared by every parser thread.
+        let dump_columns = File::open(&file_path)
+            .and_then(|file| SqlTokenizer::new(file).read_columns())
+            .unwrap_or_else(|err| panic!("Failed to read the columns of the {} dump: {}", file_type, err));
+        let cut_points = find_cut_points(&file_path, config().threads)
+            .unwrap_or_else(|err| panic!("Failed to split the {} dump: {}", file_type, err));
+        println!("Cut points: {:?}", cut_points);
+        let parts: Vec<DumpPart> = cut_points
+            .into_iter()
+            .enumerate()
+            .map(|(i, (start_offset, end_offset))| {
+                let mut file = File::open(&file_path).expect("Failed to open file");
+                let progress_handle = file.try_clone().expect("Can't clone file handle to log progress");
+                file.seek(SeekFrom::Start(start_offset)).expect("Failed to seek to start_offset");
+                let size = end_offset - start_offset;
+                // Starting on an `INSERT INTO` line, the part holds whole tuples only.
+                let reader: Box<dyn Read + Send> = Box::new(file.take(size));
+                DumpPart {
+                    file_type,
+                    tokenizer: SqlTokenizer::new(reader),
+                    progress_handle,
+                    start_offset,
+                    size,
+                    title: format!("Thread-{} {}", i + 1, label),
+                }
+            })
+            .collect();
+        (dump_columns, parts)
+    } else {
+        let dump_stream = sql_dump_stream_from_cache(file_type).await
+            .unwrap_or_else(|err| panic!("Failed to load wiki {} dump file: {}", file_type, err));
+        let mut tokenizer = SqlTokenizer::new(dump_stream.decoder);
+        let dump_columns = tokenizer.read_columns()
+            .unwrap_or_else(|err| panic!("Failed to read the columns of the {} dump: {}", file_type, err));
+        let part = DumpPart {
+            file_type,
+            tokenizer,
+            progress_handle: dump_stream.file_handle_for_progress,
+            start_offset: 0,
+            size: dump_stream.size,
+            title: label.to_string(),
+        };
+        (dump_columns, vec![part])
+    };
+    let columns = column_indices(&dump_columns, column_names)
+        .unwrap_or_else(|err| panic!("Unexpected {} dump columns: {}", file_type, err));
+
+    let parse_tuple = Arc::new(parse_tuple);
+    let handles: Vec<tokio::task::JoinHandle<T>> = parts
+        .into_iter()
+        .map(|part| {
+            let parse_tuple = Arc::clone(&parse_tuple);
+            tokio::task::spawn_blocking(move || parse_dump_part(part, columns, column_names, &*parse_tuple))
+        })
+        .collect();
+    let mut tables = Vec::with_capacity(handles.len());
+    for handle in handles {
+        tables.push(handle.await.expect("Thread panicked"));
+    }
+    tables
 }
 
-async fn parse_and_load_redirect(ctx: &mut DumpParserContext) {
-    let file_type = "redirect";
-
-    let pages_map = &ctx.pages_map;
-    let redirects_map = &mut ctx.redirects_map;
-    let mut redirects_to_resolve:Vec<WikiRedirectToResolve> = Vec::new();
-
-    let dump_stream = sql_dump_stream_from_cache(file_type).await
-        .expect(&format!("Failed to load wiki {} dump file", file_type));
-    let mut reader = BufReader::new(dump_stream.decoder);
-    let mut progress_handle = dump_stream.file_handle_for_progress;
-
-    let mut logger = DumpProgressLogger::new(dump_stream.size, "Redirects".to_string());
-    let mut count:u64 = 0;
-
-    let stream = sql_dump_parser(&mut reader, vec!["rd_from","rd_namespace","rd_title"/*,"rd_interwiki","rd_fragment"*/]).await;
-    tokio::pin!(stream);
-    while let Some(redirect_data) = stream.next().await {
-        let mut iter = redirect_data.into_iter();
-        let rd_from      = iter.next().expect("rd_from is missing");
-        let rd_namespace = iter.next().expect("rd_namespace is missing");
-        if rd_namespace != "0" {continue;}
-        let rd_title     = iter.next().expect("rd_title is missing");
-        let _to_is_redirect_option = pages_map.get(&rd_title);
-        // the redirect lead to a page that does not exist ( most likely  it's not in the article namespace)
-        if _to_is_redirect_option.is_none() {continue;}
-        let _to_is_redirect = _to_is_redirect_option.unwrap();
-        // let rd_interwiki = iter.next().expect("rd_interwiki is missing");
-        // let rd_fragment  = iter.next().expect("rd_fragment is missing");
-
-        let _from: u32 = rd_from.parse().expect("rd_from is not a valid u32");
-        let _to = _to_is_redirect.id;
-        let is_redirect = _to_is_redirect.is_redirect;
+fn parse_dump_part<T, const N: usize>(
+    part: DumpPart,
+    columns: [usize; N],
+    column_names: [&str; N],
+    parse_tuple: &impl Fn(&mut T, [Field<'_>; N]) -> bool,
+) -> T
+where
+    T: Default,
+{
+    let DumpPart { file_type, mut tokenizer, mut progress_handle, start_offset, size, title } = part;
+    let mut table = T::default();
+    let mut logger = DumpProgressLogger::new(size, title);
+    let mut count: u64 = 0;
+    while let Some(tuple) = tokenizer.next_tuple().unwrap_or_else(|err| panic!("Failed to parse the {} dump: {}", file_type, err)) {
+        let fields = std::array::from_fn(|i| tuple.get(columns[i]).unwrap_or_else(|| panic!("{} is missing", column_names[i])));
+        if !parse_tuple(&mut table, fields) {continue;}
         count += 1;
-        if (count % 65_536) == 0 {
-            let bytes_read_amount = progress_handle.stream_position().unwrap_or(0);
+        if count.is_multiple_of(65_536) {
+            let bytes_read_amount = progress_handle.stream_position().unwrap_or(0).saturating_sub(start_offset);
             logger.log(bytes_read_amount, count);
         }
-        // The redirect lead to another redirect that need to be resolved
-        if is_redirect {
-            redirects_to_resolve.push(WikiRedirectToResolve {_from_id: _from, _to_title: rd_title});
-            continue;
-         // The redirect lead to a valid page
-        } else {
-            redirects_map.insert(_from, rd_title);
-
-        }
     }
 
-    
-    let bytes_read_amount = progress_handle.stream_position().unwrap_or(0);
+    let bytes_read_amount = progress_handle.stream_position().unwrap_or(0).saturating_sub(start_offset);
     logger.log(bytes_read_amount, count);
-        
-
-    // for  wikiRedirectToResolve in redirects_to_resolve {
-    //     let _to_title = wikiRedirectToResolve._to_title;
-    //     let _to = resolveRedirect(Some(&_to_title), pages_map, redirects_map);
-    //     if _to.is_none() {continue;}
-    //     // nextBatch.push({_from, _to});
-            
-    //     count += 1;
-    //     if (count % 65_536) == 0 {
-    //         let bytes_read_amount = progress_handle.stream_position().unwrap_or(0);
-    //         logger.log(bytes_read_amount, count);
-    //     }
-        
-    // }
-
-}
-
-async fn parse_and_load_link_target(ctx: &mut DumpParserContext) {
-    let file_type = "linktarget";
-
-    let linktarget_map = &mut ctx.linktarget_map;
-
-    let dump_stream = sql_dump_stream_from_cache(file_type).await
-        .expect(&format!("Failed to load wiki {} dump file", file_type));
-    let mut reader = BufReader::new(dump_stream.decoder);
-    let mut progress_handle = dump_stream.file_handle_for_progress;
-
-    let mut logger = DumpProgressLogger::new(dump_stream.size, "Link target".to_string());
-    let mut count:u64 = 0;
-
-    let stream = sql_dump_parser(&mut reader, vec!["lt_id","lt_namespace", "lt_title" ]).await;
-    tokio::pin!(stream);
-    while let Some(linktarget_data) = stream.next().await {
-        let mut iter = linktarget_data.into_iter();
-        let raw_lt_id       = iter.next().expect("lt_id is missing");
-        let lt_namespace    = iter.next().expect("lt_namespace is missing");
-        if lt_namespace != "0" {continue;}
-        let lt_title        = iter.next().expect("lt_title is missing");
-        let lt_id = raw_lt_id.parse().expect("lt_id is not a valid u32");
-        linktarget_map.insert(lt_id,lt_title);
-        count+=1;
-        if (count % 65_536) == 0 {
-            let bytes_read_amount = progress_handle.stream_position().unwrap_or(0);
-            logger.log(bytes_read_amount, count);
+    table
+}
+
+/// Merges the maps parsed from the parts of a dump, in dump order, `merge`
+/// adding the value of a later part to the one of an earlier part.
+fn merge_parts<K: Eq + Hash, V>(parts: Vec<FxHashMap<K, V>>, merge: impl Fn(&mut V, V)) -> FxHashMap<K, V> {
+    let mut parts = parts.into_iter();
+    let mut merged = parts.next().unwrap_or_default();
+    for part in parts {
+        for (key, value) in part {
+            match merged.entry(key) {
+                Entry::Occupied(mut entry) => merge(entry.get_mut(), value),
+                Entry::Vacant(entry) => {
+                    entry.insert(value);
+                }
+            }
         }
     }
+    merged
+}
 
+async fn parse_and_load_page() -> FxHashMap<String, WikiPageId> {
+    let parts = parse_dump(
+        "page",
+        "Pages",
+        ["page_id", "page_title", "page_namespace", "page_is_redirect"],
+        |pages_map: &mut FxHashMap<String, WikiPageId>, [page_id, page_title, page_namespace, page_is_redirect]| {
+            if page_namespace.to_u32() != Some(0) {return false;}
+            let wiki_page_id = WikiPageId {
+                id: page_id.to_u32().expect("page_id is not a valid u32"),
+                is_redirect: page_is_redirect.to_u32() == Some(1),
+            };
+            pages_map.insert(page_title.to_str().expect("page_title is NULL").into_owned(), wiki_page_id);
+            true
+        },
+    )
+    .await;
+    merge_parts(parts, |page, part_page| *page = part_page)
+}
 
-    let bytes_read_amount = progress_handle.stream_position().unwrap_or(0);
-    logger.log(bytes_read_amount, count);
-    
+async fn parse_and_load_redirect() -> FxHashMap<u32, String> {
+    let parts = parse_dump(
+        "redirect",
+        "Redirects",
+        ["rd_from", "rd_namespace", "rd_title"],
+        |redirects_map: &mut FxHashMap<u32, String>, [rd_from, rd_namespace, rd_title]| {
+            if rd_namespace.to_u32() != Some(0) {return false;}
+            let _from = rd_from.to_u32().expect("rd_from is not a valid u32");
+            // Kept even when leading nowhere, `resolve_redirects` filters them once all pages are known.
+            redirects_map.insert(_from, rd_title.to_str().expect("rd_title is NULL").into_owned());
+            true
+        },
+    )
+    .await;
+    merge_parts(parts, |title, part_title| *title = part_title)
+}
 
+async fn parse_and_load_link_target() -> FxHashMap<u32, String> {
+    let parts = parse_dump(
+        "linktarget",
+        "Link target",
+        ["lt_id", "lt_namespace", "lt_title"],
+        |linktarget_map: &mut FxHashMap<u32, String>, [lt_id, lt_namespace, lt_title]| {
+            if lt_namespace.to_u32() != Some(0) {return false;}
+            let lt_id = lt_id.to_u32().expect("lt_id is not a valid u32");
+            linktarget_map.insert(lt_id, lt_title.to_str().expect("lt_title is NULL").into_owned());
+            true
+        },
+    )
+    .await;
+    merge_parts(parts, |title, part_title| *title = part_title)
 }
 
-async fn parse_and_load_page_links(ctx:Arc<DumpParserContext>) -> (&'static mut u64, &'static mut HashMap<u32, Vec<u32>, FxBuildHasher>) {
-    let file_type = "pagelinks";
+/// Link target ids of every page, resolved to page ids by `resolve_page_links`.
+async fn parse_and_load_page_links(linktarget_map: Arc<FxHashMap<u32, String>>) -> FxHashMap<u32, Vec<u32>> {
+    let parts = parse_dump(
+        "pagelinks",
+        "Page Links",
+        ["pl_from", "pl_from_namespace", "pl_target_id"],
+        move |pages_links: &mut FxHashMap<u32, Vec<u32>>, [pl_from, pl_from_namespace, pl_target_id]| {
+            if pl_from_namespace.to_u32() != Some(0) {return false;}
+            let pl_target_id = pl_target_id.to_u32().expect("pl_target_id is not a valid u32");
+            // Not an article namespace link target.
+            if !linktarget_map.contains_key(&pl_target_id) {return false;}
+            let _from = pl_from.to_u32().expect("pl_from is not a valid u32");
+            pages_links.entry(_from).or_default().push(pl_target_id);
+            true
+        },
+    )
+    .await;
+    // The links of a page can straddle two parts.
+    merge_parts(parts, |links, part_links| links.extend(part_links))
+}
 
-    let pages_map = &ctx.pages_map;
-    let linktarget_map = &ctx.linktarget_map;
-    let redirects_map= &ctx.redirects_map;
+/// Keeps the redirects leading to an existing article, redirects to another
+/// redirect are dropped.
+fn resolve_redirects(ctx: &mut DumpParserContext) {
+    let pages_map = &*ctx.pages_map;
+    ctx.redirects_map.retain(|_, rd_title| matches!(pages_map.get(rd_title), Some(page) if !page.is_redirect));
+}
 
-    let mut pages_links: &'static mut FxHashMap<u32, Vec<u32>> = Box::leak(Box::new(FxHashMap::with_hasher(FxBuildHasher::default())));
+/// Replaces the link target ids of `pages_links` by the page id they lead to,
+/// following redirects, and drops the links leading nowhere. Returns the
+/// number of links left.
+fn resolve_page_links(pages_links: &mut FxHashMap<u32, Vec<u32>>, ctx: &DumpParserContext) -> u64 {
+    let pages_map = &*ctx.pages_map;
+    let redirects_map = &*ctx.redirects_map;
+    let linktarget_map = &*ctx.linktarget_map;
     let mut links_count: u64 = 0;
-
-    let dump_stream = sql_dump_stream_from_cache(file_type).await
-        .expect(&format!("Failed to load wiki {} dump file", file_type));
-    let mut reader = BufReader::new(dump_stream.decoder);
-    let mut progress_handle = dump_stream.file_handle_for_progress;
-
-    let mut logger = DumpProgressLogger::new(dump_stream.size, "Page Links".to_string());
-    let mut count:u64 = 0;
-
-    let stream = sql_dump_parser(&mut reader, vec!["pl_from", "pl_from_namespace", "pl_target_id"]).await;
-    tokio::pin!(stream);
-    while let Some(pagelinks_data) = stream.next().await {
-        let mut iter = pagelinks_data.into_iter();
-        let pl_from             = iter.next().expect("pl_from is missing");
-        let pl_from_namespace   = iter.next().expect("pl_from_namespace is missing");
-        if pl_from_namespace != "0" {continue;}
-        let raw_pl_target_id    = iter.next().expect("pl_target_id is missing");
-        let pl_target_id :u32 = raw_pl_target_id.parse().expect("pl_target_id is not a valid u32");
-        let _to_title_option = linktarget_map.get(&pl_target_id);
-        if _to_title_option.is_none() {continue;}
-        let _to_title = _to_title_option.unwrap();
-        let _to_is_redirect_option = pages_map.get(_to_title);
-        if _to_is_redirect_option.is_none() {continue;}
-        let _to_is_redirect = _to_is_redirect_option.unwrap();
-        let _from:u32 = pl_from.parse().expect("pl_from is not a valid u32");
-        let _to = _to_is_redirect.id;
-        let is_redirect = _to_is_redirect.is_redirect;
-        links_count += 1;
-        if is_redirect {
-            let _to_resolved_option = resolve_redirect(redirects_map.get(&_to), pages_map, redirects_map);
-            if _to_resolved_option.is_none() {continue;}
-            let _to_resolved =_to_resolved_option.unwrap();
-            if !pages_links.contains_key(&_from) {
-                pages_links.insert(_from, vec![_to_resolved]);
+    for links in pages_links.values_mut() {
+        links.retain_mut(|target| {
+            let Some(_to_title) = linktarget_map.get(target) else { return false };
+            let Some(_to_is_redirect) = pages_map.get(_to_title) else { return false };
+            let _to_resolved_option = if _to_is_redirect.is_redirect {
+                resolve_redirect(redirects_map.get(&_to_is_redirect.id), pages_map, redirects_map)
             } else {
-                pages_links.entry(_from).or_default().push(_to_resolved); 
-            }
-            // nextBatch.push({_from, _to: _to_resolved});
-            // The link resolve to a valid page
-        } else {
-            if !pages_links.contains_key(&_from) {
-                pages_links.insert(_from, vec![_to]);
-            } else {
-                pages_links.entry(_from).or_default().push(_to); 
+                Some(_to_is_redirect.id)
+            };
+            match _to_resolved_option {
+                Some(_to_resolved) => {
+                    *target = _to_resolved;
+                    true
+                }
+                None => false,
             }
-        }
-        count+=1;
-        if (count % 65_536) == 0 {
-            let bytes_read_amount = progress_handle.stream_position().unwrap_or(0);
-            logger.log(bytes_read_amount, count);
-        }
-
+        });
+        links_count += links.len() as u64;
     }
-
-    let bytes_read_amount = progress_handle.stream_position().unwrap_or(0);
-    logger.log(bytes_read_amount, count);
-    let all_links_count_static: &'static mut u64 = Box::leak(Box::new(links_count));
-    (all_links_count_static, pages_links)
-
+    links_count
 }
 
 fn resolve_redirect(page_title_option:Option<&String>, pages_map: &FxHashMap<String, WikiPageId>, redirects_map: &FxHashMap<u32, String>) -> Option<u32> {
-    if page_title_option.is_none() {return None;}
-    let page_title = page_title_option.unwrap();
+    let page_title = page_title_option?;
 
     let mut n:u8 = 0;
     let page_id = pages_map.get(page_title).expect("page_title should be in pages_map").id;
     let mut last_title = redirects_map.get(&page_id);
     while n<10 {
-        if last_title.is_none() {return None;}
-        let _to_is_redirect_option = pages_map.get(last_title.unwrap());
-        if _to_is_redirect_option.is_none() {return None;}
-        let _to_is_redirect = _to_is_redirect_option.unwrap();
+        let _to_is_redirect = pages_map.get(last_title?)?;
         let id = _to_is_redirect.id;
         let is_redirect = _to_is_redirect.is_redirect;
         if !is_redirect {return Some(id);}
@@ -823,46 +643,288 @@ fn resolve_redirect(page_title_option:Option<&String>, pages_map: &FxHashMap<Str
     None
 }
 
+/// Checkpoint of the `phase` parse in the dump cache, keyed by the identity of
+/// the `(file_type, gunzipped)` dumps it depends on, or by the headers of
+/// those whose table is reused from a previous build.
+fn phase_checkpoint(phase: &str, dump_files: &[(&str, bool)], reused_dumps: &[(&str, DumpHeaders)]) -> Checkpoint {
+    let inputs = dump_files
+        .iter()
+        .map(|(file_type, gunzipped)| match reused_dumps.iter().find(|(reused_file_type, _)| reused_file_type == file_type) {
+            Some((_, headers)) => CheckpointInput::ReusedTable(headers.clone()),
+            None => CheckpointInput::Dump(dump_file_path(file_type, *gunzipped)),
+        })
+        .collect();
+    Checkpoint::new(dump_path(phase, "checkpoint.rkyv"), inputs)
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv().ok();
-    let mut ctx = DumpParserContext {
-        pages_map: Box::leak(Box::new(FxHashMap::with_hasher(FxBuildHasher::default()))),
-        redirects_map: Box::leak(Box::new(FxHashMap::with_hasher(FxBuildHasher::default()))),
-        linktarget_map: Box::leak(Box::new(FxHashMap::with_hasher(FxBuildHasher::default()))),
-        pages_links: Box::leak(Box::new(FxHashMap::with_hasher(FxBuildHasher::default()))),
-        links_count: Box::leak(Box::new(0))
+    let build_start = Instant::now();
+    let config = match Config::parse(std::env::args().skip(1)) {
+        Ok(config) => CONFIG.get_or_init(|| config),
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, cli::USAGE);
+            std::process::exit(2);
+        }
     };
-    println!("\nStart parsing pages dump...");
-    parse_and_load_page(&mut ctx).await;
-    println!("\nPages dump parsing complete!");
-    println!("\nStart parsing redirect dump...");
-    parse_and_load_redirect(&mut ctx).await;
-    println!("\nRedirect dump parsing complete!");
-    println!("\nStart parsing linktarget dump...");
-    parse_and_load_link_target(&mut ctx).await;
-    println!("\nLinktarget dump parsing complete!");
-    println!("\nStart parsing page links dump...");
-
-    let use_multithread = std::env::var("USE_MULTITHREAD").unwrap_or("0".to_string());
-    let actx = Arc::new(ctx);
-    let cctx = Arc::clone(&actx);
-    let (links_count, pages_links) = if use_multithread == "true" || use_multithread == "1" {
-        let (links_count, pages_links) = launch_multithread_pagelinks_parser(actx).await;
-        (links_count, pages_links)
-    } else {
-        let (links_count, pages_links) = parse_and_load_page_links(actx).await;
-        (links_count, pages_links)
+    match config.command {
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Command::Inspect => return inspect::inspect(&config.graph_path),
+        Command::Download | Command::Parse | Command::Build => {}
+    }
+    if config.command == Command::Build
+        && let Some(parent) = config.graph_path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    // Whether each dump is read gunzipped, as the multithreaded parsers do.
+    let gunzipped = config.multithread;
+    let dump_files = [("page", gunzipped), ("redirect", gunzipped), ("linktarget", gunzipped), ("pagelinks", gunzipped)];
+    let timings = PhaseTimings::new(build_start);
+
+    if config.command == Command::Download {
+        // Downloads are network bound, every dump is downloaded at once.
+        let downloads: Vec<_> = dump_files
+            .into_iter()
+            .map(|(file_type, gunzipped)| {
+                let timings = timings.clone();
+                tokio::spawn(async move {
+                    let download = download_dump(file_type, gunzipped);
+                    timings.time(format!("{} download", file_type), download).await.map_err(|err| err.to_string())
+                })
+            })
+            .collect();
+        for download in downloads {
+            download.await??;
+        }
+        println!("\n{}", timings.report());
+        return Ok(());
+    }
+
+    // The tables of a previous build are reused for every dump that did not
+    // change, the others are parsed again whole: dumps are only published as
+    // full snapshots, so a dump of a new date is always parsed.
+    let mut previous = match &config.reuse_from {
+        Some(path) => Some(previous_build::PreviousBuild::open(path)?),
+        None => None,
     };
-    
-    println!("\nPage links dump parsing complete!");
+    let mut reused = DumpTables::default();
+    let mut reused_dumps: Vec<(&str, DumpHeaders)> = Vec::new();
+    if let Some(previous) = &mut previous {
+        for (file_type, gunzipped) in dump_files {
+            let headers = current_dump_headers(file_type, gunzipped).await;
+            if previous.has_dump(file_type, &headers) {
+                reused_dumps.push((file_type, headers));
+            }
+        }
+        // Page links only keep the link targets known when they were parsed.
+        if !reused_dumps.iter().any(|(file_type, _)| *file_type == "linktarget") {
+            reused_dumps.retain(|(file_type, _)| *file_type != "pagelinks");
+        }
+        reused = std::mem::take(&mut previous.tables);
+    }
+    let reuse = |file_type: &str| reused_dumps.iter().any(|(reused_file_type, _)| *reused_file_type == file_type);
+
+    // Every phase output is checkpointed, a phase only runs again when its
+    // dumps changed or it is forced.
+    let forced = |phase: &str| config.from_phase.is_some_and(|from| PHASES[from..].contains(&phase));
+
+    // Every table is obtained by its own task: the dumps are all downloaded at
+    // once, the tables not depending on each other are parsed in parallel, and
+    // page links are parsed as soon as the link targets they keep are.
+    let page_task = {
+        let reused_pages = reuse("page").then(|| std::mem::take(&mut reused.pages));
+        let forced = forced("page");
+        let page_checkpoint = phase_checkpoint("page", &dump_files[..1], &reused_dumps);
+        let timings = timings.clone();
+        tokio::spawn(async move {
+            let (mut page_checkpoint, checkpointed) = page_checkpoint.load_blocking(forced || reused_pages.is_some()).await;
+            if let Some(pages_map) = reused_pages {
+                println!("\nPages dump unchanged, reusing the previous pages");
+                pages_map
+            } else if let Some(pages_map) = checkpointed {
+                println!("\nPages dump checkpoint is valid, skipping its parsing");
+                pages_map
+            } else {
+                timings.time("page download", download_dump("page", gunzipped)).await
+                    .unwrap_or_else(|err| panic!("Failed to load wiki page dump file: {}", err));
+                println!("\nStart parsing pages dump...");
+                let pages_map = timings.time("page parse", parse_and_load_page()).await;
+                println!("\nPages dump parsing complete!");
+                if let Err(err) = tokio::task::block_in_place(|| page_checkpoint.save(&pages_map)) {
+                    println!("Failed to save the page checkpoint, the next build parses its dump again: {}", err);
+                }
+                pages_map
+            }
+        })
+    };
+    let redirect_task = {
+        let reused_redirects = reuse("redirect").then(|| std::mem::take(&mut reused.redirects));
+        let forced = forced("redirect");
+        let redirect_checkpoint = phase_checkpoint("redirect", &dump_files[1..2], &reused_dumps);
+        let timings = timings.clone();
+        tokio::spawn(async move {
+            let (mut redirect_checkpoint, checkpointed) = redirect_checkpoint.load_blocking(forced || reused_redirects.is_some()).await;
+            if let Some(redirects_map) = reused_redirects {
+                println!("\nRedirect dump unchanged, reusing the previous redirects");
+                redirects_map
+            } else if let Some(redirects_map) = checkpointed {
+                println!("\nRedirect dump checkpoint is valid, skipping its parsing");
+                redirects_map
+            } else {
+                timings.time("redirect download", download_dump("redirect", gunzipped)).await
+                    .unwrap_or_else(|err| panic!("Failed to load wiki redirect dump file: {}", err));
+                println!("\nStart parsing redirect dump...");
+                let redirects_map = timings.time("redirect parse", parse_and_load_redirect()).await;
+                println!("\nRedirect dump parsing complete!");
+                if let Err(err) = tokio::task::block_in_place(|| redirect_checkpoint.save(&redirects_map)) {
+                    println!("Failed to save the redirect checkpoint, the next build parses its dump again: {}", err);
+                }
+                redirects_map
+            }
+        })
+    };
+    let linktarget_task = {
+        let reused_link_targets = reuse("linktarget").then(|| std::mem::take(&mut reused.link_targets));
+        let forced = forced("linktarget");
+        let linktarget_checkpoint = phase_checkpoint("linktarget", &dump_files[2..3], &reused_dumps);
+        let timings = timings.clone();
+        tokio::spawn(async move {
+            let (mut linktarget_checkpoint, checkpointed) = linktarget_checkpoint.load_blocking(forced || reused_link_targets.is_some()).await;
+            let linktarget_map = if let Some(linktarget_map) = reused_link_targets {
+                println!("\nLinktarget dump unchanged, reusing the previous link targets");
+                linktarget_map
+            } else if let Some(linktarget_map) = checkpointed {
+                println!("\nLinktarget dump checkpoint is valid, skipping its parsing");
+                linktarget_map
+            } else {
+                timings.time("linktarget download", download_dump("linktarget", gunzipped)).await
+                    .unwrap_or_else(|err| panic!("Failed to load wiki linktarget dump file: {}", err));
+                println!("\nStart parsing linktarget dump...");
+                let linktarget_map = timings.time("linktarget parse", parse_and_load_link_target()).await;
+                println!("\nLinktarget dump parsing complete!");
+                if let Err(err) = tokio::task::block_in_place(|| linktarget_checkpoint.save(&linktarget_map)) {
+                    println!("Failed to save the linktarget checkpoint, the next build parses its dump again: {}", err);
+                }
+                linktarget_map
+            };
+            Arc::new(linktarget_map)
+        })
+    };
+    let pagelinks_task = {
+        let reused_page_links = reuse("pagelinks").then(|| std::mem::take(&mut reused.page_links));
+        let forced = forced("pagelinks");
+        // Page links only keep the known link targets, which may be reused
+        // without their dump on disk.
+        let pagelinks_checkpoint = phase_checkpoint("pagelinks", &dump_files[2..], &reused_dumps);
+        let timings = timings.clone();
+        tokio::spawn(async move {
+            let (mut pagelinks_checkpoint, checkpointed) = pagelinks_checkpoint.load_blocking(forced || reused_page_links.is_some()).await;
+            if let Some(pages_links) = reused_page_links {
+                println!("\nPage links dump unchanged, reusing the previous page links");
+                (pages_links, linktarget_task.await.expect("The linktarget phase panicked"))
+            } else if let Some(pages_links) = checkpointed {
+                println!("\nPage links dump checkpoint is valid, skipping its parsing");
+                (pages_links, linktarget_task.await.expect("The linktarget phase panicked"))
+            } else {
+                // Downloaded while the link targets are parsed.
+                timings.time("pagelinks download", download_dump("pagelinks", gunzipped)).await
+                    .unwrap_or_else(|err| panic!("Failed to load wiki pagelinks dump file: {}", err));
+                let linktarget_map = linktarget_task.await.expect("The linktarget phase panicked");
+                println!("\nStart parsing page links dump...");
+                let pages_links = timings.time("pagelinks parse", parse_and_load_page_links(Arc::clone(&linktarget_map))).await;
+                println!("\nPage links dump parsing complete!");
+                if let Err(err) = tokio::task::block_in_place(|| pagelinks_checkpoint.save(&pages_links)) {
+                    println!("Failed to save the pagelinks checkpoint, the next build parses its dump again: {}", err);
+                }
+                (pages_links, linktarget_map)
+            }
+        })
+    };
+
+    let pages_map = page_task.await?;
+    let redirects_map = redirect_task.await?;
+    let (mut pages_links, linktarget_map) = pagelinks_task.await?;
+    drop(reused);
+    let mut ctx = DumpParserContext {
+        pages_map: Box::leak(Box::new(pages_map)),
+        redirects_map: Box::leak(Box::new(redirects_map)),
+        linktarget_map: Box::leak(Box::new(Arc::into_inner(linktarget_map).expect("Page links parsers still hold the link targets"))),
+    };
+
+    let dumps: Vec<DumpMeta> = dump_files
+        .into_iter()
+        .map(|(file_type, gunzipped)| {
+            let headers = match reused_dumps.iter().find(|(reused_file_type, _)| *reused_file_type == file_type) {
+                Some((_, headers)) => headers.clone(),
+                None => saved_dump_headers(file_type, gunzipped),
+            };
+            DumpMeta {
+                file_type: file_type.to_string(),
+                etag: headers.etag,
+                last_modified: headers.last_modified,
+            }
+        })
+        .collect();
+
+    if config.command == Command::Parse {
+        println!("\n{}", timings.report());
+        return Ok(());
+    }
+
+    if previous.is_some() || config.write_maps {
+        let start = timings.elapsed();
+        println!("\nSaving dump tables");
+        let tables = DumpTables {
+            dumps: dumps.iter().map(|dump| DumpMeta {
+                file_type: dump.file_type.clone(),
+                etag: dump.etag.clone(),
+                last_modified: dump.last_modified.clone(),
+            }).collect(),
+            pages: std::mem::take(ctx.pages_map),
+            redirects: std::mem::take(ctx.redirects_map),
+            link_targets: std::mem::take(ctx.linktarget_map),
+            page_links: std::mem::take(&mut pages_links),
+        };
+        let tables_path = DumpTables::path_for(&config.graph_path);
+        tables.save(&tables_path).expect("Failed to write dump tables");
+        *ctx.pages_map = tables.pages;
+        *ctx.redirects_map = tables.redirects;
+        *ctx.linktarget_map = tables.link_targets;
+        pages_links = tables.page_links;
+        println!("Dump tables saved to {}", tables_path.display());
+        timings.record("dump tables save", start);
+    }
 
+    let start = timings.elapsed();
+    println!("\nResolving redirects and page links");
+    resolve_redirects(&mut ctx);
+    let links_count = resolve_page_links(&mut pages_links, &ctx);
+    timings.record("resolve", start);
+
+    // Dropped before the graph is written, which may be the previous graph itself.
+    if let Some(previous) = previous.take() {
+        let start = timings.elapsed();
+        println!("\nComparing with the previous graph");
+        let reused_file_types: Vec<&str> = reused_dumps.iter().map(|(file_type, _)| *file_type).collect();
+        let report = previous.change_report(&reused_file_types, ctx.pages_map, ctx.redirects_map, &pages_links);
+        let report_path = config.graph_path.with_extension("changes.json");
+        std::fs::write(&report_path, serde_json::to_string_pretty(&report)?)?;
+        println!("Change report written to {}", report_path.display());
+        println!("pages {}, redirects {}, links {}", report["pages"], report["redirects"], report["links"]);
+        timings.record("change report", start);
+    }
+
+    let start = timings.elapsed();
     println!("\nAdding page with no links");
-    for (_page_title, wiki_page_id) in cctx.pages_map.iter() {
-        let page_id = wiki_page_id.id;
-        if !pages_links.contains_key(&page_id) {
-            pages_links.insert(page_id, vec![]);
-        }
+    for (_page_title, wiki_page_id) in ctx.pages_map.iter() {
+        pages_links.entry(wiki_page_id.id).or_default();
     }
 
     println!("\nBuilding Compressed Sparse Row Graph");
@@ -874,24 +936,44 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut page_ids: Vec<u32> = pages_links.keys().copied().collect();
     page_ids.sort_unstable();
 
-    let page_id_to_index: HashMap<u32, u32> = page_ids
+    // Only needed while building, the archive keeps the sorted page_ids instead.
+    let page_id_to_index: FxHashMap<u32, u32> = page_ids
         .iter()
         .enumerate()
         .map(|(i, page_id)| (*page_id, i as u32))
         .collect();
 
-    println!("Creating index_to_page_id");
+    println!("Creating titles");
+
+    let page_id_to_title: FxHashMap<u32, (&String, bool)> = ctx.pages_map
+        .iter()
+        .map(|(title, wiki_page_id)| (wiki_page_id.id, (title, wiki_page_id.is_redirect)))
+        .collect();
+    let mut titles: Vec<String> = page_ids
+        .iter()
+        .map(|page_id| page_id_to_title.get(page_id).map(|(title, _)| (*title).clone()).unwrap_or_default())
+        .collect();
+
+    println!("Creating title_index");
 
-    let index_to_page_id: HashMap<u32, u32> = page_id_to_index.iter().map(|(&k, &v)| (v, k)).collect();
+    let mut title_index: Vec<u32> = page_ids
+        .iter()
+        .enumerate()
+        .filter(|(_, page_id)| matches!(page_id_to_title.get(page_id), Some((_, false))))
+        .map(|(i, _)| i as u32)
+        .collect();
+    title_index.sort_unstable_by(|&a, &b| titles[a as usize].cmp(&titles[b as usize]));
+    drop(page_id_to_title);
 
     println!("Creating offsets and edges");
 
     let mut offsets:Vec<u32> = Vec::with_capacity(page_ids.len() + 1);
-    let mut edges = Vec::with_capacity((*links_count).try_into().expect("Value out of range for usize"));
+    let mut edges = Vec::with_capacity(links_count.try_into().expect("Value out of range for usize"));
     offsets.push(0);
     let mut i: u32 = 0;
 
     for page_id in &page_ids {
+        let start = edges.len();
         if let Some(links) = pages_links.get(page_id) {
             for link_page_id in links {
                 if let Some(link_index) = page_id_to_index.get(link_page_id) {
@@ -899,18 +981,47 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
         }
+        // Redirect resolution can point several links at the same page, keep
+        // each neighbour once so paths are not counted twice.
+        edges[start..].sort_unstable();
+        let mut end = start;
+        for read in start..edges.len() {
+            if end == start || edges[read] != edges[end - 1] {
+                edges[end] = edges[read];
+                end += 1;
+            }
+        }
+        edges.truncate(end);
         offsets.push(edges.len() as u32);
 
         i += 1;
-        if i % 65_536 == 0 {
+        if i.is_multiple_of(65_536) {
             logger.log(i.into(), i.into());
         }
     }
 
+    // Indices so far follow page id order, so the new index of every node
+    // read in old index order is also the list of nodes sorted by page id.
+    let node_order = config.node_order;
+    let sorted_nodes = if node_order == NodeOrder::PageId {
+        Vec::new()
+    } else {
+        println!("Reordering nodes by {:?}", node_order);
+        let order = reorder::node_order(&offsets, &edges, node_order);
+        let new_index = reorder::inverse(&order);
+        (offsets, edges) = reorder::relabel_links(&offsets, &edges, &order, &new_index);
+        page_ids = reorder::permute(&mut page_ids, &order);
+        titles = reorder::permute(&mut titles, &order);
+        for index in title_index.iter_mut() {
+            *index = new_index[*index as usize];
+        }
+        new_index
+    };
+
     println!("Creating reverse_offsets and reverse_edges");
 
     let mut reverse_offsets: Vec<u32> = Vec::with_capacity(page_ids.len() + 1);
-    let mut reverse_edges: Vec<u32> = Vec::with_capacity((*links_count).try_into().expect("Value out of range for usize"));
+    let mut reverse_edges: Vec<u32> = Vec::with_capacity(links_count.try_into().expect("Value out of range for usize"));
     reverse_offsets.push(0);
     
     // Temporary structure to store reverse adjacency list
@@ -923,7 +1034,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             reverse_adjacency_list[target_index as usize].push(source_index as u32);
         }
         i += 1;
-        if i % 65_536 == 0 {
+        if i.is_multiple_of(65_536) {
             logger.log(i.into(), i.into());
         }
     }
@@ -935,34 +1046,95 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     logger.log(i.into(), i.into());
     println!("\nBuild of Compressed Sparse Row Graph complete");
-    let graph = CsrGraph {
-        offsets,
-        edges,
-        reverse_offsets,
-        reverse_edges,
-        page_id_to_index,
-        index_to_page_id
-    };
 
-    println!("page_id_to_index len {}", graph.page_id_to_index.len());
-    println!("index_to_page_id len {}", graph.index_to_page_id.len());
+    let mut links = Adjacency::Raw { offsets, edges };
+    let mut reverse_links = Adjacency::Raw { offsets: reverse_offsets, edges: reverse_edges };
+    if config.compress {
+        println!("Compressing adjacency lists");
+        links = links.compress();
+        reverse_links = reverse_links.compress();
+    }
+
+    println!("Creating title lookup tables");
+
+    let pages: HashMap<String, WikiPageId> = ctx.pages_map
+        .iter()
+        .map(|(title, wiki_page_id)| (title.clone(), *wiki_page_id))
+        .collect();
+    let redirects: HashMap<u32, String> = ctx.redirects_map
+        .iter()
+        .map(|(page_id, title)| (*page_id, title.clone()))
+        .collect();
+
+    timings.record("graph build", start);
+
+    let meta = GraphMeta {
+        format_version: GRAPH_FORMAT_VERSION,
+        lang: config.lang.clone(),
+        dumps,
+        builder_version: BUILDER_VERSION.to_string(),
+        built_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs()),
+        build_duration_ms: build_start.elapsed().as_millis() as u64,
+        node_count: page_ids.len() as u32,
+        edge_count: links.edge_count() as u64,
+    };
 
-    println!("offsets len {}", graph.offsets.len());
-    println!("edges len {}", graph.edges.len());
-    println!("offsets last {}", graph.offsets.last().unwrap_or(&0));
-    println!("edges last {}", graph.edges.last().unwrap_or(&0));
+    let graph = CsrGraph {
+        meta,
+        links,
+        reverse_links,
+        page_ids,
+        sorted_nodes,
+        pages,
+        redirects,
+        titles,
+        title_index,
+    };
 
-    println!("reverse_offsets len {}", graph.reverse_offsets.len());
-    println!("reverse_edges len {}", graph.reverse_edges.len());
-    println!("reverse_offsets last {}", graph.reverse_offsets.last().unwrap_or(&0));
-    println!("reverse_edges last {}", graph.reverse_edges.last().unwrap_or(&0));
+    println!("meta {:?}", graph.meta);
+    println!("page_ids len {}", graph.page_ids.len());
+    println!("pages len {}", graph.pages.len());
+    println!("redirects len {}", graph.redirects.len());
+    println!("titles len {}", graph.titles.len());
+    println!("title_index len {}", graph.title_index.len());
+
+
+    for (name, adjacency) in [("links", &graph.links), ("reverse_links", &graph.reverse_links)] {
+        match adjacency {
+            Adjacency::Raw { offsets, edges } => println!("{} raw, {} offsets, {} edges", name, offsets.len(), edges.len()),
+            Adjacency::Compressed { offsets, edge_count, bytes } => println!(
+                "{} compressed, {} offsets, {} edges in {} bytes",
+                name,
+                offsets.len(),
+                edge_count,
+                bytes.len()
+            ),
+        }
+    }
     
 
+    let start = timings.elapsed();
     println!("\nSerializing graph...");
     let bytes = to_bytes::<Error>(&graph).expect("Graph RKYV serialization failed");
-    let mut file = File::create("graph.rkyv")?;
-    file.write_all(&bytes).expect("Failed to write graph to graph.rkyv");
-    println!("Graph serialized to graph.rkyv");
-
+    let mut header = [0u8; GRAPH_HEADER_LEN];
+    header[..GRAPH_MAGIC.len()].copy_from_slice(&GRAPH_MAGIC);
+    header[GRAPH_MAGIC.len()..GRAPH_MAGIC.len() + 4].copy_from_slice(&GRAPH_FORMAT_VERSION.to_le_bytes());
+    // The server may have the graph mapped and reloads it from the same path,
+    // so the new file only replaces it once complete, never in place.
+    let mut partial_path = config.graph_path.clone().into_os_string();
+    partial_path.push(".part");
+    let partial_path = PathBuf::from(partial_path);
+    let mut file = File::create(&partial_path)?;
+    file.write_all(&header).expect("Failed to write graph header");
+    file.write_all(&bytes).expect("Failed to write graph");
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&partial_path, &config.graph_path)?;
+    println!("Graph serialized to {}", config.graph_path.display());
+    timings.record("serialize", start);
+
+    println!("\n{}", timings.report());
     Ok(())
 }
@@ -0,0 +1,198 @@
+//! Parses command lines the way `main` gets them, with options given inline
+//! or as the next argument, taken from the environment, or wrong.
+//!
+//! Run with `cargo test --test cli`.
+
+#[allow(dead_code)]
+#[path = "../src/graph/reorder.rs"]
+mod reorder;
+#[allow(dead_code)]
+#[path = "../src/cli/cli.rs"]
+mod cli;
+
+use cli::{Command, Config};
+use reorder::NodeOrder;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The phases of `main`, which `cli` takes from the crate root.
+const PHASES: [&str; 4] = ["page", "redirect", "linktarget", "pagelinks"];
+
+/// Every environment variable an option falls back to.
+const ENV_NAMES: [&str; 19] = [
+    "WIKI_LANG",
+    "DUMP_DATE",
+    "DUMP_MIRROR",
+    "CACHE_DIR",
+    "GRAPH_OUTPUT",
+    "THREADS",
+    "REORDER_NODES",
+    "REUSE_FROM",
+    "FROM_PHASE",
+    "PAGE_DUMP",
+    "REDIRECT_DUMP",
+    "LINKTARGET_DUMP",
+    "PAGELINKS_DUMP",
+    "DOWNLOAD_RETRIES",
+    "USE_MULTITHREAD",
+    "COMPRESS_ADJACENCY",
+    "WRITE_MAPS",
+    "OFFLINE",
+    "ALLOW_UNVERIFIED",
+];
+
+/// Held while a test sets the environment or parses with it.
+static ENV: Mutex<()> = Mutex::new(());
+
+/// Parses `args` with only the `env` variables set among those of the options.
+fn parse_with_env(args: &[&str], env: &[(&str, &str)]) -> Result<Config, String> {
+    let _env = ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    // SAFETY: Only these tests touch the environment, one at a time under `ENV`.
+    unsafe {
+        for name in ENV_NAMES {
+            std::env::remove_var(name);
+        }
+        for (name, value) in env {
+            std::env::set_var(name, value);
+        }
+    }
+    let config = Config::parse(args.iter().map(|arg| arg.to_string()));
+    // SAFETY: As above.
+    unsafe {
+        for (name, _) in env {
+            std::env::remove_var(name);
+        }
+    }
+    config
+}
+
+fn parse(args: &[&str]) -> Result<Config, String> {
+    parse_with_env(args, &[])
+}
+
+fn parse_err(args: &[&str]) -> String {
+    parse(args).expect_err("the command line should be refused")
+}
+
+#[test]
+fn takes_values_inline_or_from_the_next_argument() {
+    let config = parse(&[
+        "build",
+        "--lang=en",
+        "--date",
+        "20240101",
+        "--threads=3",
+        "--mirror",
+        "https://mirror.example/dumps/",
+        "--reorder=bfs",
+        "--from-phase",
+        "linktarget",
+        "--pagelinks-dump=dumps/pagelinks.sql",
+        "--output",
+        "out/graph.rkyv",
+        "--compress",
+        "--multithread=true",
+        "--offline=0",
+    ])
+    .unwrap();
+    assert_eq!(config.command, Command::Build);
+    assert_eq!(config.lang, "en");
+    assert_eq!(config.date, "20240101");
+    assert_eq!(config.threads, 3);
+    assert_eq!(config.mirror, "https://mirror.example/dumps");
+    assert_eq!(config.node_order, NodeOrder::Bfs);
+    assert_eq!(config.from_phase, Some(2));
+    assert_eq!(config.local_dump("pagelinks"), Some(Path::new("dumps/pagelinks.sql")));
+    assert_eq!(config.local_dump("page"), None);
+    assert_eq!(config.graph_path, PathBuf::from("out/graph.rkyv"));
+    assert!(config.compress);
+    assert!(config.multithread);
+    assert!(!config.offline);
+    assert!(!config.write_maps);
+}
+
+#[test]
+fn defaults_to_build_with_the_last_value_given() {
+    let config = parse(&["--lang", "fr", "--lang=de", "--download-retries", "0"]).unwrap();
+    assert_eq!(config.command, Command::Build);
+    assert_eq!(config.lang, "de");
+    assert_eq!(config.download_retries, 0);
+    assert_eq!(config.date, "latest");
+    assert_eq!(config.mirror, "https://dumps.wikimedia.org");
+    assert_eq!(config.cache_dir, PathBuf::from("cache"));
+    assert_eq!(config.graph_path, PathBuf::from("graph.rkyv"));
+    assert_eq!(config.node_order, NodeOrder::PageId);
+    assert_eq!(config.from_phase, None);
+    assert_eq!(config.reuse_from, None);
+}
+
+#[test]
+fn falls_back_to_the_environment() {
+    let env = [
+        ("WIKI_LANG", "nl"),
+        ("THREADS", "2"),
+        ("USE_MULTITHREAD", "true"),
+        ("WRITE_MAPS", "1"),
+        ("COMPRESS_ADJACENCY", "yes"),
+        ("REUSE_FROM", "previous/graph.rkyv"),
+        ("PAGE_DUMP", "dumps/page.sql.gz"),
+        // Empty variables count as unset.
+        ("DUMP_DATE", ""),
+    ];
+    let config = parse_with_env(&["parse"], &env).unwrap();
+    assert_eq!(config.command, Command::Parse);
+    assert_eq!(config.lang, "nl");
+    assert_eq!(config.threads, 2);
+    assert!(config.multithread);
+    assert!(config.write_maps);
+    assert!(!config.compress);
+    assert_eq!(config.reuse_from, Some(PathBuf::from("previous/graph.rkyv")));
+    assert_eq!(config.local_dump("page"), Some(Path::new("dumps/page.sql.gz")));
+    assert_eq!(config.date, "latest");
+
+    // Arguments win over the environment.
+    let config = parse_with_env(&["--lang", "sv", "--threads=4", "--write-maps=false"], &env).unwrap();
+    assert_eq!(config.lang, "sv");
+    assert_eq!(config.threads, 4);
+    assert!(!config.write_maps);
+    assert!(config.multithread);
+}
+
+#[test]
+fn inspects_the_graph_given_as_argument() {
+    let config = parse(&["inspect", "graphs/en.rkyv", "--output", "ignored.rkyv"]).unwrap();
+    assert_eq!(config.command, Command::Inspect);
+    assert_eq!(config.graph_path, PathBuf::from("graphs/en.rkyv"));
+    // No language is needed to inspect.
+    assert_eq!(config.lang, "");
+
+    let config = parse(&["inspect", "--output=built.rkyv"]).unwrap();
+    assert_eq!(config.graph_path, PathBuf::from("built.rkyv"));
+    assert_eq!(parse(&["inspect"]).unwrap().graph_path, PathBuf::from("graph.rkyv"));
+
+    assert_eq!(parse_err(&["inspect", "a.rkyv", "b.rkyv"]), "Unexpected argument b.rkyv");
+}
+
+#[test]
+fn help_wins_over_everything_after_it() {
+    assert_eq!(parse(&["download", "-h", "--unknown"]).unwrap().command, Command::Help);
+    assert_eq!(parse(&["--help"]).unwrap().command, Command::Help);
+    assert_eq!(parse(&["help", "extra"]).unwrap().command, Command::Help);
+}
+
+#[test]
+fn refuses_wrong_command_lines() {
+    assert_eq!(parse_err(&["--lang", "en", "--verbose"]), "Unknown option --verbose");
+    assert_eq!(parse_err(&["--lang", "en", "upload"]), "Unknown command upload");
+    assert_eq!(parse_err(&["build", "--lang", "en", "extra"]), "Unexpected argument extra");
+    assert_eq!(parse_err(&["--threads", "2", "--lang"]), "--lang needs a value");
+    assert_eq!(parse_err(&["download"]), "The wiki language must be given with --lang or WIKI_LANG");
+    assert_eq!(parse_err(&["--lang", "en", "--date", "2024-01-01"]), "Invalid dump date 2024-01-01, expected YYYYMMDD or latest");
+    assert_eq!(parse_err(&["--lang", "en", "--threads", "0"]), "Invalid thread count 0");
+    assert_eq!(parse_err(&["--lang", "en", "--download-retries=-1"]), "Invalid retry count -1");
+    assert_eq!(parse_err(&["--lang", "en", "--reorder", "random"]), "Unknown node order random, expected none, degree or bfs");
+    assert_eq!(
+        parse_err(&["--lang", "en", "--from-phase", "graph"]),
+        format!("Unknown phase graph, expected one of {}", PHASES.join(", "))
+    );
+}
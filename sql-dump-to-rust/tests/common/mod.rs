@@ -0,0 +1,24 @@
+//! Helpers shared by the integration tests and the benchmarks, which include
+//! this module by path and each use only part of it.
+#![allow(dead_code)]
+
+/// Deterministic xorshift generator so every run uses the same inputs.
+pub struct XorShift(pub u64);
+
+impl XorShift {
+    fn advance(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn next(&mut self) -> u32 {
+        (self.advance() >> 32) as u32
+    }
+
+    /// Value in `0..bound`.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.advance() % bound
+    }
+}
@@ -0,0 +1,215 @@
+//! Downloads a dump from a stub HTTP server scripted to answer each request
+//! the way real mirrors misbehave, and checks every download ends with the
+//! whole dump or gives up after its retries.
+//!
+//! Run with `cargo test --test dump_download`.
+
+#[allow(dead_code)]
+#[path = "../src/dump/dump_headers.rs"]
+mod dump_headers;
+#[allow(dead_code)]
+#[path = "../src/dump/dump_download.rs"]
+mod dump_download;
+
+use dump_download::download_resumable;
+use dump_headers::DumpHeaders;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+const DUMP_LEN: usize = 1000;
+const ETAG: &str = "\"dump-v1\"";
+
+/// Range headers of a request received by the stub server.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Request {
+    range: Option<String>,
+    if_range: Option<String>,
+}
+
+fn dump() -> Vec<u8> {
+    (0..DUMP_LEN).map(|index| (index * 7 % 251) as u8).collect()
+}
+
+/// A whole HTTP response closing the connection after `body`.
+fn reply(status: &str, headers: &[(&str, String)], body: &[u8]) -> Vec<u8> {
+    let mut response = format!("HTTP/1.1 {}\r\n", status);
+    for (name, value) in headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", body.len()));
+    let mut response = response.into_bytes();
+    response.extend_from_slice(body);
+    response
+}
+
+fn full(body: &[u8]) -> Vec<u8> {
+    reply("200 OK", &[("ETag", ETAG.to_string())], body)
+}
+
+fn partial(dump: &[u8], start: usize) -> Vec<u8> {
+    let content_range = format!("bytes {}-{}/{}", start, dump.len() - 1, dump.len());
+    reply("206 Partial Content", &[("ETag", ETAG.to_string()), ("Content-Range", content_range)], &dump[start..])
+}
+
+/// Serves the scripted `replies` in order, one per connection, and records
+/// the requests. Requests beyond the script get a 404, failing the download.
+async fn serve(replies: Vec<Vec<u8>>) -> (String, Arc<Mutex<Vec<Request>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/dump.sql.gz", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let received = requests.clone();
+    tokio::spawn(async move {
+        let mut replies = replies.into_iter();
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut head = Vec::new();
+            let mut buffer = [0u8; 1024];
+            while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+                let read = stream.read(&mut buffer).await.unwrap();
+                if read == 0 {
+                    break;
+                }
+                head.extend_from_slice(&buffer[..read]);
+            }
+            let head = String::from_utf8_lossy(&head).into_owned();
+            let header = |name: &str| {
+                head.lines().find_map(|line| {
+                    let (line_name, value) = line.split_once(':')?;
+                    line_name.eq_ignore_ascii_case(name).then(|| value.trim().to_string())
+                })
+            };
+            received.lock().unwrap().push(Request { range: header("range"), if_range: header("if-range") });
+
+            let response = replies.next().unwrap_or_else(|| reply("404 Not Found", &[], b""));
+            let _ = stream.write_all(&response).await;
+            let _ = stream.shutdown().await;
+        }
+    });
+    (url, requests)
+}
+
+/// Where a test downloads its dump, in a directory of its own.
+fn dump_path(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dump-download-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join("dump.sql.gz")
+}
+
+fn part_path(path: &Path) -> PathBuf {
+    let mut part_path = path.as_os_str().to_owned();
+    part_path.push(".part");
+    PathBuf::from(part_path)
+}
+
+/// Leaves a `.part` file holding `content`, as an interrupted download of the `ETAG` dump would.
+fn write_part(path: &Path, content: &[u8]) {
+    let part_path = part_path(path);
+    std::fs::write(&part_path, content).unwrap();
+    DumpHeaders { etag: Some(ETAG.to_string()), last_modified: None }.save(&part_path).unwrap();
+}
+
+fn range_from(start: usize) -> Request {
+    Request { range: Some(format!("bytes={}-", start)), if_range: Some(ETAG.to_string()) }
+}
+
+/// Checks the download completed with the whole dump and left no partial file.
+fn assert_downloaded(path: &Path, headers: DumpHeaders) {
+    assert_eq!(std::fs::read(path).unwrap(), dump());
+    assert!(!part_path(path).exists());
+    assert_eq!(headers.etag.as_deref(), Some(ETAG));
+    let _ = std::fs::remove_dir_all(path.parent().unwrap());
+}
+
+#[tokio::test]
+async fn downloads_a_new_dump() {
+    let dump = dump();
+    let path = dump_path("new");
+    let (url, requests) = serve(vec![full(&dump)]).await;
+
+    let headers = download_resumable(&url, &path, 0).await.unwrap();
+    assert_eq!(*requests.lock().unwrap(), vec![Request::default()]);
+    assert_downloaded(&path, headers);
+}
+
+#[tokio::test]
+async fn resumes_a_partial_download_with_if_range() {
+    let dump = dump();
+    let path = dump_path("resume");
+    write_part(&path, &dump[..400]);
+    let (url, requests) = serve(vec![partial(&dump, 400)]).await;
+
+    let headers = download_resumable(&url, &path, 0).await.unwrap();
+    assert_eq!(*requests.lock().unwrap(), vec![range_from(400)]);
+    assert_downloaded(&path, headers);
+}
+
+#[tokio::test]
+async fn restarts_when_the_server_sends_the_whole_dump() {
+    // The dump changed since the part was downloaded, so If-Range gets a 200.
+    let dump = dump();
+    let path = dump_path("whole");
+    write_part(&path, &[0xff; 400]);
+    let (url, requests) = serve(vec![full(&dump)]).await;
+
+    let headers = download_resumable(&url, &path, 0).await.unwrap();
+    assert_eq!(*requests.lock().unwrap(), vec![range_from(400)]);
+    assert_downloaded(&path, headers);
+}
+
+#[tokio::test]
+async fn restarts_a_complete_part_refused_with_416() {
+    let dump = dump();
+    let path = dump_path("not-satisfiable");
+    write_part(&path, &dump);
+    let refused = reply("416 Range Not Satisfiable", &[("Content-Range", format!("bytes */{}", DUMP_LEN))], b"");
+    let (url, requests) = serve(vec![refused, full(&dump)]).await;
+
+    let headers = download_resumable(&url, &path, 1).await.unwrap();
+    assert_eq!(*requests.lock().unwrap(), vec![range_from(DUMP_LEN), Request::default()]);
+    assert_downloaded(&path, headers);
+}
+
+#[tokio::test]
+async fn restarts_when_resumed_at_the_wrong_offset() {
+    let dump = dump();
+    let path = dump_path("wrong-offset");
+    write_part(&path, &dump[..400]);
+    let (url, requests) = serve(vec![partial(&dump, 300), full(&dump)]).await;
+
+    let headers = download_resumable(&url, &path, 1).await.unwrap();
+    assert_eq!(*requests.lock().unwrap(), vec![range_from(400), Request::default()]);
+    assert_downloaded(&path, headers);
+}
+
+#[tokio::test]
+async fn resumes_after_the_connection_closes_early() {
+    let dump = dump();
+    let path = dump_path("closed-early");
+    let mut cut = full(&dump);
+    cut.truncate(cut.len() - (DUMP_LEN - 300));
+    let (url, requests) = serve(vec![cut, partial(&dump, 300)]).await;
+
+    let headers = download_resumable(&url, &path, 1).await.unwrap();
+    assert_eq!(*requests.lock().unwrap(), vec![Request::default(), range_from(300)]);
+    assert_downloaded(&path, headers);
+}
+
+#[tokio::test]
+async fn gives_up_after_the_retries_with_backoff() {
+    let path = dump_path("unavailable");
+    let unavailable = || reply("503 Service Unavailable", &[], b"");
+    let (url, requests) = serve(vec![unavailable(), unavailable(), unavailable()]).await;
+
+    let start = Instant::now();
+    let err = download_resumable(&url, &path, 2).await.unwrap_err();
+    // Waits 1s then 2s between the three requests.
+    assert!(start.elapsed() >= Duration::from_secs(3), "retried after {:?}", start.elapsed());
+    assert!(err.to_string().contains("after 2 retries: HTTP status 503"), "{}", err);
+    assert_eq!(requests.lock().unwrap().len(), 3);
+    assert!(!path.exists());
+    let _ = std::fs::remove_dir_all(path.parent().unwrap());
+}
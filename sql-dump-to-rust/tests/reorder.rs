@@ -0,0 +1,123 @@
+//! Reorders random graphs and checks the new order is a permutation of the
+//! nodes and the relabelled links are the same graph under it.
+//!
+//! Run with `cargo test --test reorder`.
+
+#[allow(dead_code)]
+#[path = "../src/graph/reorder.rs"]
+mod reorder;
+mod common;
+
+use common::XorShift;
+use reorder::{inverse, node_order, permute, relabel_links, NodeOrder};
+
+const SEEDS: u64 = 32;
+const ORDERS: [NodeOrder; 3] = [NodeOrder::PageId, NodeOrder::Degree, NodeOrder::Bfs];
+
+/// CSR of a random graph with sorted neighbour lists, some nodes linking to
+/// nothing and some linked from nowhere, so the BFS needs several roots.
+fn random_graph(rng: &mut XorShift) -> (Vec<u32>, Vec<u32>) {
+    let node_count = rng.below(200) + 1;
+    let mut offsets = vec![0];
+    let mut edges = Vec::new();
+    for _ in 0..node_count {
+        // Squared, so low indices get most links like hubs do.
+        let mut targets: Vec<u32> = (0..rng.below(6)).map(|_| (rng.below(node_count) * rng.below(node_count) / node_count) as u32).collect();
+        targets.sort_unstable();
+        targets.dedup();
+        edges.extend(targets);
+        offsets.push(edges.len() as u32);
+    }
+    (offsets, edges)
+}
+
+/// Every link as a pair of node indices, sorted.
+fn link_pairs(offsets: &[u32], edges: &[u32]) -> Vec<(u32, u32)> {
+    let mut pairs: Vec<(u32, u32)> = offsets
+        .windows(2)
+        .enumerate()
+        .flat_map(|(node, node_offsets)| edges[node_offsets[0] as usize..node_offsets[1] as usize].iter().map(move |&target| (node as u32, target)))
+        .collect();
+    pairs.sort_unstable();
+    pairs
+}
+
+#[test]
+fn orders_are_permutations_with_their_inverse() {
+    for seed in 1..=SEEDS {
+        let (offsets, edges) = random_graph(&mut XorShift(seed));
+        let node_count = offsets.len() - 1;
+        for order in ORDERS {
+            let nodes = node_order(&offsets, &edges, order);
+            let mut sorted = nodes.clone();
+            sorted.sort_unstable();
+            assert_eq!(sorted, (0..node_count as u32).collect::<Vec<_>>(), "seed {} {:?}", seed, order);
+
+            let new_index = inverse(&nodes);
+            for (new, &old) in nodes.iter().enumerate() {
+                assert_eq!(new_index[old as usize], new as u32);
+            }
+            assert_eq!(inverse(&new_index), nodes);
+        }
+    }
+}
+
+#[test]
+fn page_id_order_keeps_every_node_in_place() {
+    let (offsets, edges) = random_graph(&mut XorShift(7));
+    let nodes = node_order(&offsets, &edges, NodeOrder::PageId);
+    assert_eq!(nodes, (0..offsets.len() as u32 - 1).collect::<Vec<_>>());
+    assert_eq!(relabel_links(&offsets, &edges, &nodes, &inverse(&nodes)), (offsets, edges));
+}
+
+#[test]
+fn degree_order_puts_the_most_linked_nodes_first() {
+    for seed in 1..=SEEDS {
+        let (offsets, edges) = random_graph(&mut XorShift(seed));
+        let mut degrees: Vec<u32> = offsets.windows(2).map(|node_offsets| node_offsets[1] - node_offsets[0]).collect();
+        for &target in &edges {
+            degrees[target as usize] += 1;
+        }
+        let nodes = node_order(&offsets, &edges, NodeOrder::Degree);
+        assert!(nodes.windows(2).all(|pair| degrees[pair[0] as usize] >= degrees[pair[1] as usize]), "seed {}", seed);
+        assert_eq!(node_order(&offsets, &edges, NodeOrder::Bfs)[0], nodes[0], "seed {}", seed);
+    }
+}
+
+#[test]
+fn relabelling_preserves_every_link() {
+    for seed in 1..=SEEDS {
+        let (offsets, edges) = random_graph(&mut XorShift(seed));
+        let page_ids: Vec<u32> = (0..offsets.len() as u32 - 1).map(|index| index * 3 + 1).collect();
+        let page_links = |page_ids: &[u32], offsets: &[u32], edges: &[u32]| {
+            let mut links: Vec<(u32, u32)> = link_pairs(offsets, edges)
+                .into_iter()
+                .map(|(source, target)| (page_ids[source as usize], page_ids[target as usize]))
+                .collect();
+            links.sort_unstable();
+            links
+        };
+
+        for order in ORDERS {
+            let nodes = node_order(&offsets, &edges, order);
+            let new_index = inverse(&nodes);
+            let (new_offsets, new_edges) = relabel_links(&offsets, &edges, &nodes, &new_index);
+            assert_eq!(new_offsets.len(), offsets.len());
+            assert_eq!(new_edges.len(), edges.len());
+            for node_offsets in new_offsets.windows(2) {
+                assert!(new_edges[node_offsets[0] as usize..node_offsets[1] as usize].is_sorted(), "seed {} {:?}", seed, order);
+            }
+
+            let mut expected: Vec<(u32, u32)> = link_pairs(&offsets, &edges)
+                .into_iter()
+                .map(|(source, target)| (new_index[source as usize], new_index[target as usize]))
+                .collect();
+            expected.sort_unstable();
+            assert_eq!(link_pairs(&new_offsets, &new_edges), expected, "seed {} {:?}", seed, order);
+
+            // Values permuted along with the nodes still belong to the same links.
+            let new_page_ids = permute(&mut page_ids.clone(), &nodes);
+            assert_eq!(page_links(&new_page_ids, &new_offsets, &new_edges), page_links(&page_ids, &offsets, &edges));
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Tokenizes generated dumps whose strings hold everything a naive split
+//! would trip on, whole and cut into parts like the parser threads do.
+//!
+//! Run with `cargo test --test sql_tokenizer`.
+
+#[allow(dead_code)]
+#[path = "../src/dump/sql_tokenizer.rs"]
+mod sql_tokenizer;
+mod common;
+
+use common::XorShift;
+use sql_tokenizer::{column_indices, find_cut_points, SqlTokenizer};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const SEEDS: u64 = 64;
+const MAX_PARTS: usize = 9;
+/// Snippets strings are built from, most of them meaningful to the SQL syntax.
+const SNIPPETS: [&str; 16] = [
+    "a", "Title_", "é", "日本", ",", "(", ")", "),(", "'", "\\", "\"", ";", "\n", "\nINSERT INTO `t` VALUES (", "VALUES", "NULL",
+];
+
+type Tuple = Vec<Option<String>>;
+
+/// Escapes `value` the way mysqldump does, sometimes doubling quotes instead.
+fn quote(value: &str, rng: &mut XorShift) -> String {
+    let mut quoted = String::from("'");
+    for c in value.chars() {
+        match c {
+            '\'' if rng.below(2) == 0 => quoted.push_str("''"),
+            '\'' => quoted.push_str("\\'"),
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+fn random_tuple(rng: &mut XorShift) -> Tuple {
+    let mut title = String::new();
+    for _ in 0..rng.below(6) {
+        title.push_str(SNIPPETS[rng.below(SNIPPETS.len() as u64) as usize]);
+    }
+    vec![
+        Some(rng.below(1 << 32).to_string()),
+        Some(rng.below(3).to_string()),
+        Some(title),
+        if rng.below(4) == 0 { None } else { Some(rng.below(1000).to_string()) },
+    ]
+}
+
+/// Writes a dump of `tuples` split into `INSERT INTO` statements, as mysqldump lays them out.
+fn write_dump(path: &Path, tuples: &[Tuple], rng: &mut XorShift) {
+    let mut dump = String::from(
+        "-- MySQL dump 10.19\n\
+         /*!40101 SET @OLD_CHARACTER_SET_CLIENT=@@CHARACTER_SET_CLIENT */;\n\
+         DROP TABLE IF EXISTS `t`;\n\
+         CREATE TABLE `t` (\n\
+         \x20 `t_id` int(10) unsigned NOT NULL,\n\
+         \x20 `t_namespace` int(11) NOT NULL DEFAULT 0,\n\
+         \x20 `t_title` varbinary(255) NOT NULL DEFAULT '',\n\
+         \x20 `t_count` int(11) DEFAULT NULL,\n\
+         \x20 PRIMARY KEY (`t_id`)\n\
+         ) ENGINE=InnoDB DEFAULT CHARSET=binary;\n\
+         LOCK TABLES `t` WRITE;\n",
+    );
+    let mut tuples = tuples.iter().peekable();
+    while tuples.peek().is_some() {
+        dump.push_str("INSERT INTO `t` VALUES ");
+        for index in 0..=rng.below(20) {
+            let Some(tuple) = tuples.next() else { break };
+            if index > 0 {
+                dump.push(',');
+            }
+            let fields: Vec<String> = tuple
+                .iter()
+                .enumerate()
+                .map(|(column, field)| match field {
+                    None => "NULL".to_string(),
+                    Some(title) if column == 2 => quote(title, rng),
+                    Some(number) => number.clone(),
+                })
+                .collect();
+            dump.push_str(&format!("({})", fields.join(",")));
+        }
+        dump.push_str(";\n");
+    }
+    dump.push_str("UNLOCK TABLES;\n/*!40101 SET CHARACTER_SET_CLIENT=@OLD_CHARACTER_SET_CLIENT */;\n-- Dump completed\n");
+    File::create(path).unwrap().write_all(dump.as_bytes()).unwrap();
+}
+
+fn tokenize(reader: impl Read) -> Vec<Tuple> {
+    let mut tokenizer = SqlTokenizer::new(reader);
+    let mut tuples = Vec::new();
+    while let Some(tuple) = tokenizer.next_tuple().unwrap() {
+        let fields = (0..).map_while(|index| tuple.get(index)).map(|field| field.to_str().map(|value| value.into_owned()));
+        tuples.push(fields.collect());
+    }
+    tuples
+}
+
+fn tokenize_range(path: &Path, start: u64, end: u64) -> Vec<Tuple> {
+    let mut file = File::open(path).unwrap();
+    file.seek(SeekFrom::Start(start)).unwrap();
+    tokenize(file.take(end - start))
+}
+
+fn dump_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("sql-tokenizer-{}-{}.sql", std::process::id(), name))
+}
+
+#[test]
+fn tokenizes_whole_dumps() {
+    for seed in 1..=SEEDS {
+        let mut rng = XorShift(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        let tuples: Vec<Tuple> = (0..rng.below(400)).map(|_| random_tuple(&mut rng)).collect();
+        let path = dump_path(&format!("whole-{}", seed));
+        write_dump(&path, &tuples, &mut rng);
+
+        let parsed = tokenize(File::open(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(parsed, tuples, "seed {}", seed);
+    }
+}
+
+#[test]
+fn parts_hold_the_same_tuples() {
+    for seed in 1..=SEEDS {
+        let mut rng = XorShift(seed.wrapping_mul(0x2545_f491_4f6c_dd1d));
+        let tuples: Vec<Tuple> = (0..1 + rng.below(400)).map(|_| random_tuple(&mut rng)).collect();
+        let path = dump_path(&format!("parts-{}", seed));
+        write_dump(&path, &tuples, &mut rng);
+        let single = tokenize(File::open(&path).unwrap());
+
+        for parts in 1..=MAX_PARTS {
+            let cut_points = find_cut_points(&path, parts).unwrap();
+            assert!(cut_points.len() <= parts, "seed {}, {} parts: {:?}", seed, parts, cut_points);
+            assert!(cut_points.windows(2).all(|pair| pair[0].1 == pair[1].0), "seed {}: {:?}", seed, cut_points);
+            let multi: Vec<Tuple> =
+                cut_points.iter().flat_map(|(start, end)| tokenize_range(&path, *start, *end)).collect();
+            assert_eq!(multi, single, "seed {}, {} parts: {:?}", seed, parts, cut_points);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
+
+#[test]
+fn empty_dumps_have_no_parts() {
+    let mut rng = XorShift(0x5eed);
+    let path = dump_path("empty");
+    write_dump(&path, &[], &mut rng);
+    let cut_points = find_cut_points(&path, 4).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(cut_points.is_empty(), "{:?}", cut_points);
+}
+
+#[test]
+fn unescapes_strings() {
+    let dump = "INSERT INTO `t` VALUES (1,'it\\'s','a\\\\b\\n\\0','', NULL,'NULL','x''y'),(2,'),(3,''',NULL);\n\
+                INSERT INTO `t` VALUES (4,'\\%\\_','\\Z\\t');\n";
+    let parsed = tokenize(dump.as_bytes());
+    let some = |value: &str| Some(value.to_string());
+    assert_eq!(
+        parsed,
+        vec![
+            vec![some("1"), some("it's"), some("a\\b\n\0"), some(""), None, some("NULL"), some("x'y")],
+            vec![some("2"), some("),(3,'"), None],
+            vec![some("4"), some("\\%\\_"), some("\x1a\t")],
+        ]
+    );
+}
+
+#[test]
+fn rejects_truncated_dumps() {
+    let mut tokenizer = SqlTokenizer::new("INSERT INTO `t` VALUES (1,'unterminated".as_bytes());
+    assert!(tokenizer.next_tuple().is_err());
+}
+
+#[test]
+fn reads_columns_and_integers() {
+    let mut rng = XorShift(0x5eed);
+    let path = dump_path("columns");
+    write_dump(&path, &[vec![Some("4294967295".to_string()), Some("0".to_string()), Some("12".to_string()), None]], &mut rng);
+    let mut tokenizer = SqlTokenizer::new(File::open(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+
+    let columns = tokenizer.read_columns().unwrap();
+    assert_eq!(columns, ["t_id", "t_namespace", "t_title", "t_count"]);
+    assert_eq!(column_indices(&columns, ["t_count", "t_id"]).unwrap(), [3, 0]);
+    assert!(column_indices(&columns, ["t_missing"]).is_err());
+
+    let tuple = tokenizer.next_tuple().unwrap().unwrap();
+    assert_eq!(tuple.get(0).unwrap().to_u32(), Some(u32::MAX));
+    assert_eq!(tuple.get(1).unwrap().to_u32(), Some(0));
+    // Quoted digits are a string, not an integer column.
+    assert_eq!(tuple.get(2).unwrap().to_u32(), None);
+    assert_eq!(tuple.get(3).unwrap().to_u32(), None);
+    assert!(tuple.get(4).is_none());
+    assert!(tokenizer.next_tuple().unwrap().is_none());
+
+    let overflow = "INSERT INTO `t` VALUES (4294967296);\n";
+    let mut tokenizer = SqlTokenizer::new(overflow.as_bytes());
+    assert_eq!(tokenizer.next_tuple().unwrap().unwrap().get(0).unwrap().to_u32(), None);
+}